@@ -1,4 +1,4 @@
-use config::{Tweeted, UserMap};
+use config::{Digests, Tweeted, UserMap};
 use egg_mode::{Token, tweet};
 use errors::*;
 use util::SyncFile;
@@ -60,12 +60,17 @@ pub fn clear(tweeted: &mut SyncFile<Tweeted>, token: &Token) -> Result<()> {
     }
 }
 
-pub fn clear_users(users: &mut SyncFile<UserMap>) -> Result<()> {
+pub fn clear_users(users: &mut SyncFile<UserMap>, digests: &mut SyncFile<Digests>) -> Result<()> {
     println!("clearing all the following information");
 
     for user in users.values_mut() {
         user.clear();
     }
 
-    users.commit()
+    for digest in digests.values_mut() {
+        digest.pending.clear();
+    }
+
+    users.commit()?;
+    digests.commit()
 }
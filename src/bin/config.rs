@@ -1,7 +1,11 @@
-use either::{Either, Left, Right};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
 use errors::*;
-use iba_kyuko_bot::Kyuko;
+use iba_kyuko_bot::{Kyuko, KyukoKind, Periods, Timetable};
+use iba_kyuko_bot::normalize::{FoldOptions, fold, fold_kana, normalize};
+use regex::{self, RegexBuilder};
 use schedule::UnitSchedule;
+use serde::{Deserialize, Deserializer, Serializer};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::{self, Formatter, Write};
 use std::fs::{File, OpenOptions};
@@ -17,9 +21,33 @@ pub enum Follow {
         title: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         lecturer: Option<String>,
+        /// Terms that exclude information containing any of them in its title or lecturer.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        excludes: Vec<String>,
     },
     #[serde(rename = "tweet_id")]
     TweetId(u64),
+    /// Information whose normalized title matches the regular expression. Katakana in both the pattern and the
+    /// title are regarded as hiragana if `FoldOptions::kana` is set, as with the other follows.
+    #[serde(rename = "regex")]
+    Regex {
+        pattern: String,
+    },
+    /// Information matching all of the specified conditions.
+    #[serde(rename = "filter")]
+    Filter {
+        /// A part of the name of the department.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dept: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<KyukoKind>,
+        /// The day of the week, as the number of days from Monday.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        weekday: Option<u32>,
+        /// Periods at least one of which the information must cover.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        periods: Option<Periods>,
+    },
 }
 
 #[derive(Clone, Serialize, Deserialize, Eq, PartialEq)]
@@ -28,6 +56,7 @@ pub struct FollowEntry(pub Follow, pub MessageMethod);
 pub enum FollowError {
     AlreadyFollowing(String),
     TweetDoesNotExist(u64),
+    InvalidPattern(String),
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
@@ -45,7 +74,86 @@ pub struct Settings {
     pub token: Token<'static>,
     #[serde(default = "default_user_agent")]
     pub user_agent: String,
+    #[serde(default)]
     pub urls: Vec<String>,
+    /// A page of the website whose department tabs are crawled in addition to `urls`.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// The number of rows of a page allowed to fail to be parsed before archiving withdrawn information of the
+    /// page is skipped.
+    #[serde(default = "default_max_row_failures")]
+    pub max_row_failures: usize,
+    /// The timetable used for departments not listed in `timetables`.
+    #[serde(default)]
+    pub timetable: Timetable,
+    /// Timetables of departments whose campus has a different timetable, keyed by the name of the department.
+    #[serde(default)]
+    pub timetables: HashMap<String, Timetable>,
+    /// How titles and lecturers are compared with the patterns of `Follow`s.
+    #[serde(default)]
+    pub matching: FoldOptions,
+    /// When reminders of `Follow::TweetId`s are sent.
+    #[serde(default)]
+    pub reminder: Reminder,
+}
+
+/// The time to send a reminder of a piece of information, relative to its date.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Reminder {
+    /// The number of days before the date, e.g. `1` for the evening before.
+    #[serde(default)]
+    pub days_before: u32,
+    /// The time of the day in the form of `HH:MM`.
+    #[serde(serialize_with = "serialize_hm", deserialize_with = "deserialize_hm")]
+    pub time: NaiveTime,
+}
+
+/// A user's preference of when to be reminded.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum RemindPreference {
+    /// At an absolute time relative to the date of the information.
+    #[serde(rename = "at")]
+    At(Reminder),
+    /// The number of minutes before the start of the first period of the information.
+    #[serde(rename = "before_period")]
+    BeforePeriod(u32),
+}
+
+/// Compiled patterns of `Follow::Regex`es keyed by the pattern, so that each pattern is compiled only once.
+#[derive(Default)]
+pub struct RegexCache {
+    regexes: RefCell<HashMap<String, Option<regex::Regex>>>,
+}
+
+/// The maximum length of the pattern of `Follow::Regex`, in characters.
+pub const REGEX_LENGTH_LIMIT: usize = 100;
+
+/// The maximum size of a compiled `Follow::Regex`, in bytes.
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+impl Default for Reminder {
+    fn default() -> Self {
+        Reminder {
+            days_before: 1,
+            time: NaiveTime::from_hms(20, 0, 0),
+        }
+    }
+}
+
+fn serialize_hm<S: Serializer>(time: &NaiveTime, s: S) -> ::std::result::Result<S::Ok, S::Error> {
+    s.serialize_str(&time.format("%H:%M").to_string())
+}
+
+fn deserialize_hm<D: Deserializer>(d: D) -> ::std::result::Result<NaiveTime, D::Error> {
+    use serde::de::Error;
+
+    let s = String::deserialize(d)?;
+    NaiveTime::parse_from_str(&s, "%H:%M").map_err(|e| D::Error::custom(format!("invalid time {:?}: {}", s, e)))
+}
+
+/// A few broken rows are tolerated so that a single row that never parses does not stop archiving for good.
+fn default_max_row_failures() -> usize {
+    3
 }
 
 pub fn default_user_agent() -> String {
@@ -67,6 +175,17 @@ pub struct UserInfo {
         FollowEntry
     >,
     pub next_id: u64,
+    /// The time of the day to send the digest of new information matching the user's follows, or `None` if the
+    /// digest is disabled. While it is enabled, new information is collected into the digest regardless of the
+    /// `MessageMethod` of each follow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest_time: Option<NaiveTime>,
+    /// When to send reminders, or `None` to follow `Settings::reminder`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remind: Option<RemindPreference>,
+    /// The IDs of the Tweets followed by `Follow::TweetId` whose reminders have already been sent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reminded: Vec<u64>,
     // TODO: rate limit
 }
 
@@ -75,8 +194,31 @@ pub type UserMap = HashMap<
     UserInfo,
 >;
 
+/// Returns the information posted as the Tweet `tweet_id`, along with the key of `tweeted` it is stored under.
+pub fn find_tweeted(tweeted: &Tweeted, tweet_id: u64) -> Option<(&str, &Kyuko)> {
+    let id = tweet_id.to_string();
+    tweeted.iter().filter_map(|(dept, kyukos)| kyukos.get(&id).map(|k| (dept.as_str(), k))).next()
+}
+
+/// Information waiting to be sent in the digest of a user.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Digest {
+    /// The IDs of the Tweets of the information.
+    pub pending: Vec<u64>,
+    /// The last date on which the digest was due, so that it is sent at most once a day.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_sent: Option<NaiveDate>,
+}
+
+pub type Digests = HashMap<
+    String, // user id
+    Digest,
+>;
+
 /// Load configuration files under the specified directory.
-pub fn load<P: AsRef<Path>>(working_dir: P) -> Result<(SyncFile<Tweeted>, SyncFile<UserMap>, Settings, File)> {
+pub fn load<P: AsRef<Path>>(working_dir: P)
+    -> Result<(SyncFile<Tweeted>, SyncFile<UserMap>, SyncFile<Digests>, Settings, File)>
+{
     use std::fs;
 
     let path = working_dir.as_ref();
@@ -96,22 +238,91 @@ pub fn load<P: AsRef<Path>>(working_dir: P) -> Result<(SyncFile<Tweeted>, SyncFi
 
     let tweeted = for_file!("tweets.yml", SyncFile::new).chain_err(|| "unable to open tweets.yml")?;
     let users = for_file!("users.yml", SyncFile::new).chain_err(|| "unable to open users.yml")?;
+    let digests = for_file!("digests.yml", SyncFile::new).chain_err(|| "unable to open digests.yml")?;
     let settings: Settings = ::yaml::from_reader(
         for_file!("settings.yml", File::open).chain_err(|| "unable to open settings.yml")?
     ).chain_err(|| "failed to load settings.yml")?;
+    if settings.urls.is_empty() && settings.base_url.is_none() {
+        return Err("settings.yml: either `urls` or `base_url` must be specified".into());
+    }
     let archive = for_file!("archive.tsv", |path| OpenOptions::new().append(true).create(true).open(path))
         .chain_err(|| "unable to open archive.tsv")?;
 
-    Ok((tweeted, users, settings, archive))
+    Ok((tweeted, users, digests, settings, archive))
 }
 
 impl Follow {
-    pub fn matches(&self, k: &Kyuko) -> bool {
-        if let Follow::Pattern { ref title, ref lecturer } = *self {
-            k.title.contains(title) && lecturer.as_ref().map_or(true, |l| k.lecturer.contains(l))
-        } else {
-            false
+    /// Returns whether `k` of the department `dept` matches the pattern, comparing the strings folded with
+    /// `options`. The patterns of `Follow::Regex`es are compiled through `regexes`.
+    pub fn matches(&self, dept: &str, k: &Kyuko, options: FoldOptions, regexes: &RegexCache) -> bool {
+        use chrono::Datelike;
+
+        match *self {
+            Follow::Pattern { ref title, ref lecturer, ref excludes } => {
+                let (k_title, k_lecturer) = (fold(&k.title, options), fold(&k.lecturer, options));
+                k_title.contains(&fold(title, options))
+                    && lecturer.as_ref().map_or(true, |l| k_lecturer.contains(&fold(l, options)))
+                    && !excludes.iter().any(|e| {
+                        let e = fold(e, options);
+                        k_title.contains(&e) || k_lecturer.contains(&e)
+                    })
+            },
+            Follow::Regex { ref pattern } => {
+                let title = normalize(&k.title);
+                let title = if options.kana { fold_kana(&title) } else { title };
+                regexes.is_match(pattern, options, &title)
+            },
+            Follow::Filter { dept: ref d, ref kind, weekday, ref periods } => {
+                d.as_ref().map_or(true, |d| fold(dept, options).contains(&fold(d, options)))
+                    && kind.as_ref().map_or(true, |kind| k.kind == *kind)
+                    && weekday.map_or(true, |w| {
+                        // Information spanning multiple days matches if any of the days is the weekday.
+                        let days = k.until.map_or(0, |until| until.signed_duration_since(k.date).num_days()).max(0);
+                        (0..days.min(6) + 1).any(|i| (k.date.weekday().num_days_from_monday() + i as u32) % 7 == w)
+                    })
+                    && periods.as_ref().map_or(true, |p| k.periods.overlaps(p))
+            },
+            Follow::TweetId(_) => false,
+        }
+    }
+}
+
+/// Compiles the pattern of `Follow::Regex` in the way `options` specifies, rejecting too long or too large ones.
+///
+/// The compiled regex is to be matched against the title folded with `fold_kana` if `options.kana` is set.
+pub fn compile_regex(pattern: &str, options: FoldOptions) -> ::std::result::Result<regex::Regex, String> {
+    if pattern.is_empty() {
+        return Err("the pattern is empty".to_owned());
+    } else if pattern.chars().count() > REGEX_LENGTH_LIMIT {
+        return Err(format!("the pattern is longer than {} characters", REGEX_LENGTH_LIMIT));
+    }
+
+    let pattern = if options.kana { fold_kana(pattern) } else { pattern.to_owned() };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(options.case)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+impl RegexCache {
+    /// Returns whether `text` matches `pattern`, which is compiled with `options` on the first use. An invalid
+    /// pattern matches nothing.
+    ///
+    /// `options` must be the same for every call since it is not a part of the key of the cache.
+    pub fn is_match(&self, pattern: &str, options: FoldOptions, text: &str) -> bool {
+        let mut regexes = self.regexes.borrow_mut();
+
+        if !regexes.contains_key(pattern) {
+            let re = compile_regex(pattern, options)
+                .map_err(|e| warn!("invalid pattern {:?}: {}", pattern, e))
+                .ok();
+            regexes.insert(pattern.to_owned(), re);
         }
+
+        regexes[pattern].as_ref().map_or(false, |re| re.is_match(text))
     }
 }
 
@@ -132,20 +343,56 @@ impl fmt::Debug for Settings {
             .field("schedule", &self.schedule)
             .field("admins", &self.admins)
             .field("user_agent", &self.user_agent)
-            .field("urls", &self.user_agent)
+            .field("urls", &self.urls)
+            .field("base_url", &self.base_url)
+            .field("max_row_failures", &self.max_row_failures)
+            .field("timetable", &self.timetable)
+            .field("timetables", &self.timetables)
+            .field("matching", &self.matching)
+            .field("reminder", &self.reminder)
             .finish()
     }
 }
 
+impl Reminder {
+    /// Returns the date and time to remind of information dated `date`.
+    pub fn time_for(&self, date: NaiveDate) -> NaiveDateTime {
+        (date - Duration::days(self.days_before as i64)).and_time(self.time)
+    }
+}
+
+impl Settings {
+    /// Returns the timetable of the department.
+    pub fn timetable(&self, dept: &str) -> &Timetable {
+        self.timetables.get(dept).unwrap_or(&self.timetable)
+    }
+}
+
 impl UserInfo {
+    /// Returns the date and time to remind the user of `k`, whose department has the timetable `timetable`.
+    ///
+    /// `RemindPreference::BeforePeriod` falls back to `default` if the start time of `k` is unknown.
+    pub fn remind_time(&self, k: &Kyuko, timetable: &Timetable, default: &Reminder) -> NaiveDateTime {
+        match self.remind {
+            Some(RemindPreference::At(ref r)) => r.time_for(k.date),
+            Some(RemindPreference::BeforePeriod(minutes)) => match timetable.ranges(&k.periods) {
+                Some(ranges) => k.date.and_time(ranges[0].0) - Duration::minutes(minutes as i64),
+                None => default.time_for(k.date),
+            },
+            None => default.time_for(k.date),
+        }
+    }
+
     pub fn clear(&mut self) {
         self.following.clear();
         self.following.shrink_to_fit();
         self.next_id = 0;
+        self.reminded.clear();
     }
 
-    pub fn follow<'a>(&mut self, target: Follow, via: MessageMethod, tweeted: &'a SyncFile<Tweeted>)
-    -> ::std::result::Result<(String, Either<(String, Option<String>), (u64, &'a Kyuko)>), FollowError>
+    /// Registers `target` and returns its ID. The pattern of a `Follow::Regex` is validated with `options`.
+    pub fn follow(&mut self, target: Follow, via: MessageMethod, tweeted: &Tweeted, options: FoldOptions)
+        -> ::std::result::Result<String, FollowError>
     {
         use self::Follow::*;
         use self::FollowError::*;
@@ -165,22 +412,165 @@ impl UserInfo {
             }
         }
 
-        let ret = match target.clone() {
-            Pattern { title, lecturer } => Left((title, lecturer)),
-            TweetId(id) => {
-                let id_str = id.to_string();
-                if let Some(k) = tweeted.values().filter_map(|kyukos| kyukos.get(&id_str)).next() {
-                    Right((id, k))
-                } else {
-                    return Err(TweetDoesNotExist(id));
-                }
+        match target {
+            Pattern { .. } | Filter { .. } => (),
+            TweetId(id) => if find_tweeted(tweeted, id).is_none() {
+                return Err(TweetDoesNotExist(id));
             },
-        };
+            Regex { ref pattern } => {
+                compile_regex(pattern, options).map_err(InvalidPattern)?;
+            },
+        }
 
         let id = self.next_id.to_string();
         self.following.insert(id.clone(), FollowEntry(target, via));
         self.next_id += 1;
 
-        Ok((id, ret))
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use chrono::NaiveDate;
+    use iba_kyuko_bot::{Kyuko, KyukoKind, Periods};
+    use iba_kyuko_bot::normalize::FoldOptions;
+    use super::*;
+
+    /// Returns a cancellation of `線形代数` by `山田` on 2017-06-12, 3講時, to be modified by each test.
+    pub fn kyuko() -> Kyuko {
+        Kyuko {
+            kind: KyukoKind::Cancellation,
+            date: NaiveDate::from_ymd(2017, 6, 12),
+            until: None,
+            periods: Periods::from(vec![3]),
+            title: "線形代数".to_owned(),
+            lecturer: "山田".to_owned(),
+            remarks: None,
+        }
+    }
+
+    #[test]
+    fn compile_regex_test() {
+        use std::iter;
+
+        let opts = FoldOptions::default();
+        let repeat = |c, n| iter::repeat(c).take(n).collect::<String>();
+
+        assert!(compile_regex("^英語(I|II)$", opts).is_ok());
+        assert!(compile_regex(&repeat('a', REGEX_LENGTH_LIMIT), opts).is_ok());
+        assert!(compile_regex(&repeat('あ', REGEX_LENGTH_LIMIT), opts).is_ok());
+
+        assert!(compile_regex("", opts).is_err());
+        assert!(compile_regex(&repeat('a', REGEX_LENGTH_LIMIT + 1), opts).is_err());
+        assert!(compile_regex(r"\w{1000}", opts).is_err());
+        assert!(compile_regex("(", opts).is_err());
+
+        assert!(compile_regex("ai", opts).unwrap().is_match("AI入門"));
+        assert!(!compile_regex("ai", FoldOptions { case: false, kana: true }).unwrap().is_match("AI入門"));
+    }
+
+    #[test]
+    fn matches_pattern() {
+        let regexes = RegexCache::default();
+        let opts = FoldOptions::default();
+        let pattern = |title: &str, lecturer: Option<&str>, excludes: &[&str]| Follow::Pattern {
+            title: title.to_owned(),
+            lecturer: lecturer.map(ToOwned::to_owned),
+            excludes: excludes.iter().map(|&e| e.to_owned()).collect(),
+        };
+
+        macro_rules! test_eq {
+            ($f:expr, $k:expr, $expect:expr) => {{
+                assert_eq!($f.matches("教養教育", &$k, opts, &regexes), $expect);
+            }};
+        }
+
+        let k = Kyuko { title: "英語IIＢ（中級）".to_owned(), lecturer: "Ｊ．Ｓｍｉｔｈ・山田".to_owned(), ..kyuko() };
+        test_eq!(pattern("英語", None, &[]), k, true);
+        test_eq!(pattern("英語", Some("smith"), &[]), k, true);
+        test_eq!(pattern("英語", Some("佐藤"), &[]), k, false);
+
+        // An exclusion term in the title,
+        test_eq!(pattern("英語", None, &["中級"]), k, false);
+        test_eq!(pattern("英語", None, &["上級"]), k, true);
+        // in the lecturer,
+        test_eq!(pattern("英語", None, &["山田"]), k, false);
+        test_eq!(pattern("英語", Some("山田"), &["smith"]), k, false);
+        // and in full-width text folded along with the term.
+        test_eq!(pattern("英語", None, &["iib"]), k, false);
+        test_eq!(pattern("英語", None, &["Ｊ.ＳＭＩＴＨ"]), k, false);
+        test_eq!(pattern("英語", None, &["上級", "(中級)"]), k, false);
+    }
+
+    #[test]
+    fn matches_regex() {
+        let regexes = RegexCache::default();
+        let regex = |pattern: &str| Follow::Regex { pattern: pattern.to_owned() };
+        let k = Kyuko { title: "エイゴ　Ⅱ".to_owned(), ..kyuko() };
+
+        assert!(regex("^えいご II$").matches("教養教育", &k, FoldOptions::default(), &regexes));
+        assert!(regex("^エイゴ").matches("教養教育", &k, FoldOptions::default(), &regexes));
+        assert!(!regex("線形").matches("教養教育", &k, FoldOptions::default(), &regexes));
+
+        let regexes = RegexCache::default();
+        let opts = FoldOptions { case: true, kana: false };
+        assert!(!regex("えいご").matches("教養教育", &k, opts, &regexes));
+        assert!(regex("エイゴ").matches("教養教育", &k, opts, &regexes));
+    }
+
+    #[test]
+    fn matches_filter() {
+        let regexes = RegexCache::default();
+        let opts = FoldOptions::default();
+        let filter = |dept: Option<&str>, kind: Option<KyukoKind>, weekday: Option<u32>, periods: Option<Vec<u8>>| {
+            Follow::Filter {
+                dept: dept.map(ToOwned::to_owned),
+                kind: kind,
+                weekday: weekday,
+                periods: periods.map(Periods::from),
+            }
+        };
+
+        macro_rules! test_eq {
+            ($f:expr, $dept:expr, $k:expr, $expect:expr) => {{
+                assert_eq!($f.matches($dept, &$k, opts, &regexes), $expect);
+            }};
+        }
+
+        // 2017-06-12 is a Monday.
+        let k = kyuko();
+        test_eq!(filter(None, None, None, None), "教養教育", k, true);
+        test_eq!(filter(Some("教養"), None, None, None), "教養教育", k, true);
+        test_eq!(filter(Some("工学"), None, None, None), "教養教育", k, false);
+        test_eq!(filter(Some("ｸﾞﾛｰﾊﾞﾙ"), None, None, None), "グローバル教育センター", k, true);
+        test_eq!(filter(None, Some(KyukoKind::Cancellation), None, None), "教養教育", k, true);
+        test_eq!(filter(None, Some(KyukoKind::MakeUp), None, None), "教養教育", k, false);
+        test_eq!(filter(None, None, Some(0), None), "教養教育", k, true);
+        test_eq!(filter(None, None, Some(1), None), "教養教育", k, false);
+        test_eq!(filter(None, None, None, Some(vec![3, 4])), "教養教育", k, true);
+        test_eq!(filter(None, None, None, Some(vec![1, 2])), "教養教育", k, false);
+        test_eq!(filter(Some("教養"), None, Some(0), Some(vec![2])), "教養教育", k, false);
+
+        let k = Kyuko { periods: Periods::Morning, ..kyuko() };
+        test_eq!(filter(None, None, None, Some(vec![1])), "教養教育", k, true);
+        test_eq!(filter(None, None, None, Some(vec![3])), "教養教育", k, false);
+
+        // From Friday to Monday across the weekend.
+        let k = Kyuko {
+            date: NaiveDate::from_ymd(2017, 6, 16),
+            until: Some(NaiveDate::from_ymd(2017, 6, 19)),
+            ..kyuko()
+        };
+        test_eq!(filter(None, None, Some(4), None), "教養教育", k, true);
+        test_eq!(filter(None, None, Some(5), None), "教養教育", k, true);
+        test_eq!(filter(None, None, Some(6), None), "教養教育", k, true);
+        test_eq!(filter(None, None, Some(0), None), "教養教育", k, true);
+        test_eq!(filter(None, None, Some(1), None), "教養教育", k, false);
+        test_eq!(filter(None, None, Some(3), None), "教養教育", k, false);
+
+        // Ranges of a week or longer cover every day of the week.
+        let k = Kyuko { until: Some(NaiveDate::from_ymd(2017, 6, 30)), ..kyuko() };
+        test_eq!(filter(None, None, Some(3), None), "教養教育", k, true);
     }
 }
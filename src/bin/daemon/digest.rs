@@ -0,0 +1,111 @@
+use chrono::Local;
+use config::*;
+use egg_mode::direct;
+use errors::*;
+use super::{format_entry, tweet_url};
+use util::SyncFile;
+
+/// Sends the digests whose time has come, at most once a day for each user.
+///
+/// Information withdrawn before the digest is sent is omitted. A digest longer than `dm_text_limit` is split into
+/// several direct messages. Information still pending after the user has disabled the digest is sent right away.
+pub fn send(users: &SyncFile<UserMap>, digests: &mut SyncFile<Digests>, tweeted: &SyncFile<Tweeted>,
+    settings: &Settings, screen_name: &str, dm_text_limit: usize) -> Result<()>
+{
+    let now = Local::now().naive_local();
+    let mut changed = false;
+
+    for (user_id, u) in users.iter() {
+        let due = match u.digest_time {
+            Some(time) => now.time() >= time && digests.get(user_id).map_or(true, |d| d.last_sent != Some(now.date())),
+            None => digests.get(user_id).map_or(false, |d| !d.pending.is_empty()),
+        };
+
+        if !due {
+            continue;
+        }
+
+        let digest = digests.entry(user_id.clone()).or_insert_with(Digest::default);
+        if u.digest_time.is_some() {
+            digest.last_sent = Some(now.date());
+        }
+        changed = true;
+
+        let lines = digest.pending.drain(..)
+            .filter_map(|id| {
+                find_tweeted(tweeted, id).map(|(dept, k)| format_entry(dept, k, &tweet_url(screen_name, id)))
+            })
+            .collect::<Vec<_>>();
+
+        if lines.is_empty() {
+            continue;
+        }
+
+        let uid: u64 = user_id.parse().chain_err(|| format!("invalid user ID in {:?}", users.file_name()))?;
+
+        for text in split_messages(&format!("【ダイジェスト】新着情報{}件", lines.len()), &lines, dm_text_limit) {
+            if let Err(e) = direct::send(uid, &text, &settings.token.clone().into()) {
+                warn!("failed to send a direct message {:?}\ncaused by: {:?}", text, e);
+            }
+        }
+        info!("sent a digest of {} item(s) to {}", lines.len(), user_id);
+    }
+
+    if changed {
+        digests.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Joins `lines` after `header` into messages each of which has at most `limit` characters.
+fn split_messages(header: &str, lines: &[String], limit: usize) -> Vec<String> {
+    use util;
+
+    let mut ret = Vec::new();
+    let mut cur = header.to_owned();
+
+    for l in lines {
+        if cur.chars().count() + 1 + l.chars().count() > limit && !cur.is_empty() {
+            ret.push(cur);
+            cur = String::new();
+        }
+        if !cur.is_empty() {
+            cur.push('\n');
+        }
+        cur.push_str(l);
+    }
+
+    if !cur.is_empty() {
+        ret.push(cur);
+    }
+
+    for m in &mut ret {
+        util::shorten(m, limit);
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_messages_test() {
+        let lines = ["・線形代数", "・微分積分学", "・英語", "・物理学実験", "・化学"]
+            .iter()
+            .map(|&l| l.to_owned())
+            .collect::<Vec<_>>();
+
+        for &limit in &[20, 15, 11] {
+            let messages = split_messages("【ダイジェスト】", &lines, limit);
+
+            assert!(messages.iter().all(|m| m.chars().count() <= limit), "{:?}", messages);
+            assert_eq!(messages.join("\n"), format!("【ダイジェスト】\n{}", lines.join("\n")));
+        }
+
+        let messages = split_messages("【ダイジェスト】", &lines, 1000);
+        assert_eq!(messages.len(), 1);
+    }
+}
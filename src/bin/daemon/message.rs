@@ -1,5 +1,8 @@
 use config::*;
+use chrono::{Local, NaiveDate};
+use daemon::{Department, State, WDAYS, format_entry, format_periods, tweet_url};
 use errors::*;
+use iba_kyuko_bot::{Kyuko, KyukoKind, Periods};
 use std::borrow::Cow;
 use std::fmt::Write;
 use twitter_stream::User;
@@ -18,11 +21,13 @@ macro_rules! respondln {
     };
 }
 
+/// Processes the commands in `text` and returns the response, which should fit in `limit` characters.
 pub fn message(via: MessageMethod, text: &str, sender: User, recipient_screen_name: String,
-    in_reply_to: Option<StatusId>, users: &mut SyncFile<UserMap>, tweeted: &mut SyncFile<Tweeted>, settings: &Settings)
-    -> Result<String>
+    in_reply_to: Option<StatusId>, users: &mut SyncFile<UserMap>, digests: &mut SyncFile<Digests>,
+    tweeted: &mut SyncFile<Tweeted>, settings: &Settings, state: &State, limit: usize) -> Result<String>
 {
     use admin;
+    use iba_kyuko_bot::normalize::normalize;
     use std::fmt::Write;
     use std::process;
 
@@ -30,11 +35,17 @@ pub fn message(via: MessageMethod, text: &str, sender: User, recipient_screen_na
 
     let mut resp = String::new();
     let lang = &sender.lang;
+    // Whether the commands have modified the sender's `UserInfo`.
+    let mut modified = false;
 
     macro_rules! sender_info {
         () => (users.entry(sender.id.to_string()).or_insert_with(UserInfo::default));
     }
 
+    // Phones tend to input full-width characters, e.g. `２０：００` for `20:00`, which are normalized here once and for
+    // all so that the commands need not care about them.
+    let text = normalize(text);
+
     for stmt in text.split(';').map(str::trim).filter(|s| !s.is_empty()) {
         let mut tokens = stmt.split(' ').filter(|s| !s.is_empty());
 
@@ -43,118 +54,393 @@ pub fn message(via: MessageMethod, text: &str, sender: User, recipient_screen_na
         }
 
         match tokens.next() {
-            Some("follow") => follow(
-                tokens, &mut resp, via, in_reply_to, sender_info!(), lang, &recipient_screen_name, tweeted
-            )?,
-            Some("unfollow") => unfollow(tokens, &mut resp, in_reply_to, sender_info!(), lang, &recipient_screen_name)?,
+            Some("follow") => {
+                follow(
+                    tokens, &mut resp, via, in_reply_to, sender.id, sender_info!(), lang, &recipient_screen_name,
+                    tweeted, settings, &state.regexes
+                )?;
+                modified = true;
+            },
+            Some("unfollow") => {
+                unfollow(tokens, &mut resp, in_reply_to, sender_info!(), lang, &recipient_screen_name)?;
+                modified = true;
+            },
             Some("clear") => {
                 sender_info!().clear();
+                modified = true;
+                // The information waiting for the digest is no longer followed either.
+                let cleared = match digests.get_mut(&sender.id.to_string()) {
+                    Some(d) => {
+                        d.pending.clear();
+                        true
+                    },
+                    None => false,
+                };
+                if cleared {
+                    digests.commit()?;
+                }
                 respondln!(
                     resp, lang,
                     "全ての講座の情報のフォローを解除しました。", "You have unfollowed all the lecture information."
                 );
             },
             Some("list") => list(&mut resp, sender_info!(), lang, &recipient_screen_name)?,
+            Some("remind") => {
+                remind(tokens, &mut resp, sender_info!(), lang, settings)?;
+                modified = true;
+            },
+            Some("digest") => {
+                digest(tokens, &mut resp, sender_info!(), lang)?;
+                modified = true;
+            },
+            Some("search") => search(tokens, &mut resp, lang, &recipient_screen_name, tweeted, settings, limit)?,
+            Some("today") => date(tokens, "today", 0, &mut resp, lang, &recipient_screen_name, tweeted, limit)?,
+            Some("tomorrow") => date(tokens, "tomorrow", 1, &mut resp, lang, &recipient_screen_name, tweeted, limit)?,
             Some("rem") => (), // noop
             Some("admin") if settings.admins.contains(&sender.id) => match tokens.next() {
                 Some("clear") => admin::clear(tweeted, &settings.token.clone().into())?,
-                Some("clear-users") => admin::clear_users(users)?,
+                Some("clear-users") => admin::clear_users(users, digests)?,
                 Some("remove") => admin::remove(tokens, tweeted, &settings.token.clone().into())?,
+                Some("departments") => list_departments(&mut resp, &state.departments, lang)?,
                 Some("shutdown") => process::exit(0), // TODO: graceful shutdown
                 Some(cmd) => unknown!(cmd),
                 None => (),
             },
-            Some(cmd) => unknown!(cmd),
+            Some(cmd) => {
+                let today = Local::today().naive_local();
+                match parse_date(cmd, today) {
+                    Some(d) => {
+                        let offset = d.signed_duration_since(today).num_days();
+                        date(tokens, cmd, offset, &mut resp, lang, &recipient_screen_name, tweeted, limit)?
+                    },
+                    None => unknown!(cmd),
+                }
+            },
             None => (),
         }
     }
 
+    if modified {
+        users.commit()?;
+    }
+
     Ok(resp)
 }
 
-fn follow<'a, I: Iterator<Item=&'a str>>(mut tokens: I, resp: &mut String, via: MessageMethod,
-    in_reply_to: Option<StatusId>, sender: &mut UserInfo, lang: &str, recipient_screen_name: &str,
-    tweeted: &SyncFile<Tweeted>) -> Result<()>
+/// Lists the information whose title or lecturer contains the query.
+fn search<'a, I: Iterator<Item=&'a str>>(tokens: I, resp: &mut String, lang: &str, recipient_screen_name: &str,
+    tweeted: &Tweeted, settings: &Settings, limit: usize) -> Result<()>
+{
+    use iba_kyuko_bot::normalize::fold;
+
+    let (terms, page) = page_arg(tokens);
+    if terms.is_empty() {
+        respondln!(resp, lang, "検索語を指定してください（例：`search 線形代数`）", "Specify a query (e.g. `search Calculus`).");
+        return Ok(());
+    }
+
+    let query = fold(&terms.join(" "), settings.matching);
+    let today = Local::today().naive_local();
+    let entries = tweeted.iter()
+        .flat_map(|(dept, kyukos)| kyukos.iter().map(move |(id, k)| (dept.as_str(), id.as_str(), k)))
+        .filter(|&(_, _, k)| k.until.unwrap_or(k.date) >= today)
+        .filter(|&(_, _, k)| {
+            fold(&k.title, settings.matching).contains(&query) || fold(&k.lecturer, settings.matching).contains(&query)
+        })
+        .collect();
+
+    let cmd = format!("search {}", terms.join(" "));
+    respond_entries(resp, lang, recipient_screen_name, entries, page, &cmd, limit)
+}
+
+/// Lists the information on the date `offset` days after today.
+fn date<'a, I: Iterator<Item=&'a str>>(tokens: I, cmd: &str, offset: i64, resp: &mut String, lang: &str,
+    recipient_screen_name: &str, tweeted: &Tweeted, limit: usize) -> Result<()>
+{
+    use chrono::Duration;
+
+    let (_, page) = page_arg(tokens);
+    let date = Local::today().naive_local() + Duration::days(offset);
+    let entries = tweeted.iter()
+        .flat_map(|(dept, kyukos)| kyukos.iter().map(move |(id, k)| (dept.as_str(), id.as_str(), k)))
+        .filter(|&(_, _, k)| k.date <= date && date <= k.until.unwrap_or(k.date))
+        .collect();
+
+    respond_entries(resp, lang, recipient_screen_name, entries, page, cmd, limit)
+}
+
+/// Splits the arguments into the terms and the page number given as the last argument like `p2`, which defaults to 1.
+/// `p0` is regarded as the first page.
+fn page_arg<'a, I: Iterator<Item=&'a str>>(tokens: I) -> (Vec<&'a str>, usize) {
+    let mut terms = tokens.collect::<Vec<_>>();
+
+    let page = terms.last().and_then(|t| if t.starts_with('p') { t[1..].parse::<usize>().ok() } else { None });
+    match page {
+        Some(p) => {
+            terms.pop();
+            (terms, p.max(1))
+        },
+        None => (terms, 1),
+    }
+}
+
+/// Parses a date like `6/12`, `6月12日` or `2017-06-12`, inferring the year from `today` if omitted.
+fn parse_date(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    use iba_kyuko_bot::scraper::infer_year;
+
+    if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d").or_else(|_| NaiveDate::parse_from_str(s, "%Y/%m/%d")) {
+        return Some(d);
+    }
+
+    let nums = s.trim_right_matches('日')
+        .split(|c: char| c == '/' || c == '月')
+        .map(|n| n.parse::<u32>().ok())
+        .collect::<Vec<_>>();
+    match (nums.len(), nums.get(0).cloned(), nums.get(1).cloned()) {
+        (2, Some(Some(m)), Some(Some(d))) => infer_year(m, d, today),
+        _ => None,
+    }
+}
+
+/// Lists `entries` of (department, Tweet ID, information), showing the `page`th page of those that fit in `limit`
+/// characters along with the rest of `resp`.
+///
+/// The length is counted in characters, the same unit as `util::shorten` truncating the response, so that a page is
+/// never cut off in the middle.
+fn respond_entries(resp: &mut String, lang: &str, recipient_screen_name: &str, mut entries: Vec<(&str, &str, &Kyuko)>,
+    page: usize, cmd: &str, limit: usize) -> Result<()>
+{
+    /// Room for the header and the footer of a page, except for `cmd` repeated in the footer.
+    const PAGE_MARGIN: usize = 40;
+
+    if entries.is_empty() {
+        respondln!(resp, lang, "該当する情報はありません。", "No information found.");
+        return Ok(());
+    }
+
+    entries.sort_by(|a, b| (a.2.date, a.0).cmp(&(b.2.date, b.0)));
+
+    let lines = entries.iter().map(|&(dept, id, k)| {
+        let (kind, datefmt) = if lang.starts_with("en") {
+            (k.kind.en(), k.date.format("%b %-d"))
+        } else {
+            (k.kind.ja(), k.date.format("%-m/%-d"))
+        };
+        format!(
+            "・{} {} {}／{}：{} [{}] {}",
+            datefmt, format_periods(&k.periods), dept, kind, k.title, k.lecturer, tweet_url(recipient_screen_name, id)
+        )
+    }).collect::<Vec<_>>();
+
+    // Split the lines into pages, each of which has at least one line.
+    let room = limit.saturating_sub(resp.chars().count() + PAGE_MARGIN + cmd.chars().count());
+    let mut pages = vec![Vec::new()];
+    let mut len = 0;
+    for l in &lines {
+        let n = l.chars().count() + 1;
+        if len + n > room && !pages.last().unwrap().is_empty() {
+            pages.push(Vec::new());
+            len = 0;
+        }
+        pages.last_mut().unwrap().push(l);
+        len += n;
+    }
+
+    let total = pages.len();
+    let page = page.min(total);
+
+    respondln!(
+        resp, lang,
+        "{}件（{}/{}ページ）", "{} result(s) (page {}/{})",
+        lines.len(), page, total
+    );
+    for l in &pages[page - 1] {
+        respondln!(resp, lang, "{}", "{}", l);
+    }
+    if page < total {
+        respondln!(resp, lang, "続き：`{} p{}`", "Next: `{} p{}`", cmd, page + 1);
+    }
+
+    Ok(())
+}
+
+fn follow<'a, I: Iterator<Item=&'a str>>(tokens: I, resp: &mut String, via: MessageMethod,
+    in_reply_to: Option<StatusId>, sender_id: u64, sender: &mut UserInfo, lang: &str, recipient_screen_name: &str,
+    tweeted: &SyncFile<Tweeted>, settings: &Settings, regexes: &RegexCache) -> Result<()>
 {
     use self::Follow::*;
 
+    // `--notify` anywhere in the statement also sends the information already posted to the sender.
+    let tokens = tokens.collect::<Vec<_>>();
+    let notify_to = if tokens.contains(&"--notify") { Some(sender_id) } else { None };
+    let tokens = tokens.into_iter().filter(|&t| t != "--notify");
+
     fn register_inner(f: Follow, via: MessageMethod, sender: &mut UserInfo, resp: &mut String, lang: &str,
-        recipient_screen_name: &str, tweeted: &SyncFile<Tweeted>) -> Result<()>
+        recipient_screen_name: &str, tweeted: &SyncFile<Tweeted>, settings: &Settings, regexes: &RegexCache,
+        notify_to: Option<u64>) -> Result<()>
     {
-        use either::{Left, Right};
+        use self::Follow::*;
         use self::FollowError::*;
 
-        match sender.follow(f, via, tweeted) {
-            Ok((id, Left((title, Some(lecturer))))) => respondln!(
+        let result = sender.follow(f.clone(), via, tweeted, settings.matching);
+        let followed = result.is_ok();
+
+        match (result, f.clone()) {
+            (Ok(id), Pattern { title, lecturer: Some(lecturer), excludes }) => respondln!(
                 resp, lang,
-                "題目が「{}」を含み担当教員「{}」を含む講座の情報を通知します（ID: {}）。",
+                "題目が「{}」を含み担当教員「{}」を含む講座の情報を通知します{}（ID: {}）。",
                 "You will be notified of information about lectures containing \"{}\"
-                    in their title and \"{}\" in their lecturer's name (ID: \"{}\").",
-                title, lecturer, id
+                    in their title and \"{}\" in their lecturer's name{} (ID: \"{}\").",
+                title, lecturer, format_excludes(&excludes, lang), id
             ),
-            Ok((id, Left((title, None)))) => respondln!(
+            (Ok(id), Pattern { title, lecturer: None, excludes }) => respondln!(
                 resp, lang,
-                "題目が「{}」を含む講座の情報を通知します（ID: {}）。",
+                "題目が「{}」を含む講座の情報を通知します{}（ID: {}）。",
                 "You will be notified of information about lectures containing \"{}\"
-                    in their title (ID: \"{}\")",
-                title, id
+                    in their title{} (ID: \"{}\")",
+                title, format_excludes(&excludes, lang), id
+            ),
+            (Ok(id), Regex { pattern }) => respondln!(
+                resp, lang,
+                "題目が正規表現 /{}/ に一致する講座の情報を通知します（ID: {}）。",
+                "You will be notified of information about lectures whose title matches /{}/ (ID: \"{}\")",
+                pattern, id
+            ),
+            (Ok(id), Filter { dept, kind, weekday, periods }) => respondln!(
+                resp, lang,
+                "条件「{}」に一致する講座の情報を通知します（ID: {}）。",
+                "You will be notified of information about lectures matching \"{}\" (ID: \"{}\")",
+                format_filter(&dept, &kind, weekday, &periods), id
             ),
-            Ok((_, Right((tweet_id, k)))) => {
-                let datefmt = if lang.starts_with("en") {
-                    k.date.format("%a, %b %d-, %Y")
+            (Ok(_), TweetId(tweet_id)) => if let Some((dept, k)) = find_tweeted(tweeted, tweet_id) {
+                let at = sender.remind_time(k, settings.timetable(dept), &settings.reminder);
+                let (kind, atfmt) = if lang.starts_with("en") {
+                    (k.kind.en(), at.format("%a, %b %-d, %Y %-H:%M"))
                 } else {
-                    k.date.format("%Y年%-m月%-d日")
+                    (k.kind.ja(), at.format("%Y年%-m月%-d日 %-H:%M"))
                 };
                 respondln!(
                     resp, lang,
-                    "follow: 講座「{} [{}]」についての{}情報\
-                        （https://twitter.com/{}/status/{}）を{}に通知します。",
-                    "You will be reminded of the {2} information of the lecture \
-                        \"{0}\" by {1} (https://twitter.com/{3}/status/{4}), on {5}.",
-                    k.title, k.lecturer, k.kind, recipient_screen_name, tweet_id, datefmt
+                    "follow: 講座「{} [{}]」についての{}情報（{}）を{}にリマインドします。",
+                    "You will be reminded of the {2} information of the lecture \"{0}\" by {1} ({3}) at {4}.",
+                    k.title, k.lecturer, kind, tweet_url(recipient_screen_name, tweet_id), atfmt
                 );
             },
-            Err(AlreadyFollowing(id)) => respondln!(
+            (Err(AlreadyFollowing(id)), _) => respondln!(
                 resp, lang,
                 "既にフォローしている情報です（ID: {}）",
                 "You are already following the information (ID: \"{}\")",
                 id
             ),
-            Err(TweetDoesNotExist(tweet_id)) => respondln!(
+            (Err(TweetDoesNotExist(tweet_id)), _) => respondln!(
                 resp, lang,
-                "https://twitter.com/{}/status/{}の情報は存在しないか、\
-                    または既に掲示が終了しています。",
-                "The lecture information of https://twitter.com/{}/status/{} \
-                    does not exist or has been withdrawn.",
-                recipient_screen_name, tweet_id
+                "{}の情報は存在しないか、または既に掲示が終了しています。",
+                "The lecture information of {} does not exist or has been withdrawn.",
+                tweet_url(recipient_screen_name, tweet_id)
             ),
+            (Err(InvalidPattern(e)), f) => respondln!(
+                resp, lang,
+                "正規表現 /{}/ は使用できません：{}",
+                "The regular expression /{}/ is not allowed: {}",
+                if let Regex { ref pattern } = f { pattern.as_str() } else { "" }, e
+            ),
+        }
+
+        if followed {
+            list_matching(&f, resp, lang, recipient_screen_name, tweeted, settings, regexes, notify_to)?;
         }
 
         Ok(())
     }
 
     macro_rules! register {
-        ($f:expr) => (register_inner($f, via, sender, resp, lang, recipient_screen_name, tweeted)?);
+        ($f:expr) => (
+            register_inner(
+                $f, via, sender, resp, lang, recipient_screen_name, tweeted, settings, regexes, notify_to
+            )?
+        );
     }
 
+    // The conditions of `Follow::Filter`, which make up a single follow along with the other conditions.
+    let (mut dept, mut kind, mut weekday, mut periods) = (None, None, None, None);
+    let mut args = Vec::new();
+
+    for arg in tokens {
+        let (key, value) = match filter_term(arg) {
+            Some(term) => term,
+            None => {
+                args.push(arg);
+                continue;
+            },
+        };
+
+        let valid = match key {
+            "in" => {
+                dept = Some(value.to_owned());
+                true
+            },
+            "kind" => {
+                kind = parse_kind(value);
+                kind.is_some()
+            },
+            "on" => {
+                weekday = parse_weekday(value);
+                weekday.is_some()
+            },
+            _ => {
+                periods = value.parse::<Periods>().ok();
+                periods.is_some()
+            },
+        };
+
+        if !valid {
+            respondln!(
+                resp, lang,
+                "不正な条件です：{}（例：`kind:休講`、`on:火`、`period:3-4`）",
+                "Invalid condition: {} (e.g. `kind:cancellation`, `on:Tue`, `period:3-4`)",
+                arg
+            );
+            return Ok(());
+        }
+    }
+
+    if dept.is_some() || kind.is_some() || weekday.is_some() || periods.is_some() {
+        if args.is_empty() {
+            register!(Filter { dept: dept, kind: kind, weekday: weekday, periods: periods });
+        } else {
+            respondln!(
+                resp, lang,
+                "`in:`などの条件は題目などと同時に指定できません：{}",
+                "Conditions like `in:` cannot be combined with titles or other arguments: {}",
+                args.join(" ")
+            );
+        }
+        return Ok(());
+    }
+
+    let mut tokens = args.into_iter();
     let mut title: Option<&str> = None;
+    let mut lecturer: Option<&str> = None;
+    let mut excludes: Vec<&str> = Vec::new();
+
+    // Registers the pending pattern, if any.
+    macro_rules! flush {
+        () => {
+            if let Some(t) = title.take() {
+                register!(Pattern {
+                    title: t.to_owned(),
+                    lecturer: lecturer.take().map(ToOwned::to_owned),
+                    excludes: excludes.drain(..).map(ToOwned::to_owned).collect(),
+                });
+            }
+        };
+    }
 
     while let Some(arg) = tokens.next() {
         match arg {
-            "by" => {
-                if let Some(t) = title.take() {
-                    register!(Pattern {
-                        title: t.to_owned(),
-                        lecturer: tokens.next().map(ToOwned::to_owned)
-                    });
-                } else {
-                    title = Some(arg);
-                }
-            },
+            "by" if title.is_some() && lecturer.is_none() => lecturer = tokens.next(),
             "tweet" => {
-                if let Some(t) = title.take() {
-                    register!(Pattern { title: t.to_owned(), lecturer: None });
-                }
+                flush!();
 
                 if let Some(id) = in_reply_to {
                     register!(TweetId(id));
@@ -166,45 +452,205 @@ fn follow<'a, I: Iterator<Item=&'a str>>(mut tokens: I, resp: &mut String, via:
                     }
                 }
             },
+            // e.g. `/^英語(I|II)/`
+            t if t.len() >= 2 && t.starts_with('/') && t.ends_with('/') => {
+                flush!();
+                register!(Regex { pattern: t[1..t.len()-1].to_owned() });
+            },
+            // An exclusion term of the pending pattern, e.g. `-中級`.
+            t if t.len() >= 2 && t.starts_with('-') => excludes.push(&t[1..]),
             t => {
-                if let Some(t) = title {
-                    register!(Pattern { title: t.to_owned(), lecturer: None });
-                }
+                flush!();
                 title = Some(t);
             },
         }
     }
 
-    if let Some(t) = title {
-        register!(Pattern { title: t.to_owned(), lecturer: None });
+    flush!();
+
+    Ok(())
+}
+
+/// Lists the information in `tweeted` that matches `f` and has not passed yet, and also sends them to the user
+/// `notify_to` in a direct message if specified.
+fn list_matching(f: &Follow, resp: &mut String, lang: &str, recipient_screen_name: &str, tweeted: &Tweeted,
+    settings: &Settings, regexes: &RegexCache, notify_to: Option<u64>) -> Result<()>
+{
+    use chrono::Local;
+
+    /// The maximum number of information listed in a response.
+    const LIST_LIMIT: usize = 5;
+    /// The maximum number of information sent to `notify_to`.
+    const NOTIFY_LIMIT: usize = 10;
+
+    if let Follow::TweetId(_) = *f {
+        return Ok(());
+    }
+
+    let today = Local::today().naive_local();
+    let mut matched = tweeted.iter()
+        .flat_map(|(dept, kyukos)| kyukos.iter().map(move |(id, k)| (dept, id, k)))
+        .filter(|&(dept, _, k)| k.until.unwrap_or(k.date) >= today && f.matches(dept, k, settings.matching, regexes))
+        .collect::<Vec<_>>();
+
+    if matched.is_empty() {
+        return Ok(());
+    }
+
+    matched.sort_by_key(|&(_, _, k)| k.date);
+
+    respondln!(
+        resp, lang,
+        "現在掲示中の該当する情報（{}件）：", "Matching information currently posted ({}):", matched.len()
+    );
+
+    for &(_, id, k) in matched.iter().take(LIST_LIMIT) {
+        let (kind, datefmt) = if lang.starts_with("en") {
+            (k.kind.en(), k.date.format("%b %-d"))
+        } else {
+            (k.kind.ja(), k.date.format("%-m月%-d日"))
+        };
+        respondln!(
+            resp, lang, "・{} {}：{} {}", "* {} {}: {} {}", datefmt, kind, k.title, tweet_url(recipient_screen_name, id)
+        );
+    }
+
+    if matched.len() > LIST_LIMIT {
+        respondln!(resp, lang, "…ほか{}件", "...and {} more", matched.len() - LIST_LIMIT);
+    }
+
+    // The information is sent in a single direct message so that following a broad pattern does not flood the user.
+    if let Some(user_id) = notify_to {
+        let mut text = format!("【現在掲示中の情報】{}件", matched.len());
+        for &(dept, id, k) in matched.iter().take(NOTIFY_LIMIT) {
+            text.push('\n');
+            text.push_str(&format_entry(dept, k, &tweet_url(recipient_screen_name, id)));
+        }
+        if matched.len() > NOTIFY_LIMIT {
+            write!(text, "\n…ほか{}件", matched.len() - NOTIFY_LIMIT).chain_err(|| WRITE_FAILED)?;
+        }
+        super::notify(user_id, MessageMethod::Dm, &text, settings);
     }
 
     Ok(())
 }
 
+/// Formats the exclusion terms of `Follow::Pattern`, e.g. `（「中級」を除く）`, or an empty string if there are none.
+fn format_excludes(excludes: &[String], lang: &str) -> String {
+    if excludes.is_empty() {
+        String::new()
+    } else if lang.starts_with("en") {
+        format!(" excluding {}", excludes.iter().map(|e| format!("\"{}\"", e)).collect::<Vec<_>>().join(", "))
+    } else {
+        format!("（{}を除く）", excludes.iter().map(|e| format!("「{}」", e)).collect::<Vec<_>>().join("・"))
+    }
+}
+
+/// Splits a condition of `Follow::Filter` like `in:工学部`, `kind:休講`, `on:火` or `period:3-4` into the key and
+/// the value.
+fn filter_term(term: &str) -> Option<(&str, &str)> {
+    const KEYS: [&'static str; 4] = ["in", "kind", "on", "period"];
+
+    let (key, value) = match term.find(':') {
+        Some(i) => (&term[..i], &term[i+1..]),
+        None => return None,
+    };
+
+    if KEYS.contains(&key) && !value.is_empty() {
+        Some((key, value))
+    } else {
+        None
+    }
+}
+
+/// Parses a day of the week like `火`, `火曜日` or `Tue` into the number of days from Monday.
+fn parse_weekday(s: &str) -> Option<u32> {
+    const EN: [&'static str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+    let ja = s.trim_right_matches("曜日").trim_right_matches('曜');
+    let en = s.to_lowercase();
+    WDAYS.iter().position(|&w| w == ja)
+        .or_else(|| EN.iter().position(|&w| en.starts_with(w)))
+        .map(|i| i as u32)
+}
+
+/// Parses the kind of information given by its Japanese or English label, e.g. `休講`, `make-up class` or
+/// `roomchange`. Unknown kinds are rejected.
+fn parse_kind(s: &str) -> Option<KyukoKind> {
+    use iba_kyuko_bot::KyukoKind::*;
+
+    if let Other(_) = KyukoKind::from_label(s) {
+        let en = s.to_lowercase().replace(|c: char| c == '-' || c == '_' || c.is_whitespace(), "");
+        match en.as_str() {
+            "cancellation" | "cancel" => Some(Cancellation),
+            "makeup" | "makeupclass" => Some(MakeUp),
+            "roomchange" => Some(RoomChange),
+            "timechange" => Some(TimeChange),
+            "other" | "misc" => Some(Misc),
+            _ => None,
+        }
+    } else {
+        Some(KyukoKind::from_label(s))
+    }
+}
+
+/// Formats the conditions of `Follow::Filter` in the syntax of the `follow` command.
+fn format_filter(dept: &Option<String>, kind: &Option<KyukoKind>, weekday: Option<u32>, periods: &Option<Periods>)
+    -> String
+{
+    let mut ret = Vec::new();
+
+    if let Some(ref d) = *dept {
+        ret.push(format!("in:{}", d));
+    }
+    if let Some(ref k) = *kind {
+        ret.push(format!("kind:{}", k));
+    }
+    if let Some(w) = weekday {
+        ret.push(format!("on:{}", WDAYS[w as usize % 7]));
+    }
+    if let Some(ref p) = *periods {
+        ret.push(format!("period:{}", p));
+    }
+
+    ret.join(" ")
+}
+
 fn unfollow<'a, I, S>(tokens: I, resp: &mut String, in_reply_to: Option<StatusId>, sender: &mut UserInfo, lang: &str,
     recipient_screen_name: &str) -> Result<()>
     where I: Iterator<Item=S>, S: Into<Cow<'a, str>>
 {
     for id in tokens.map(Into::into).chain(in_reply_to.map(|id| id.to_string().into())) {
         match sender.following.remove(id.as_ref()).map(|ent| ent.0) {
-            Some(Follow::Pattern { title, lecturer: None }) => respondln!(
+            Some(Follow::Pattern { title, lecturer: None, excludes }) => respondln!(
                 resp, lang,
-                "ID {}（{}）の情報のフォローを解除しました。",
-                "Unfollowed lecture information of \"{}\": \"{}\".",
-                id, title
+                "ID {}（{}{}）の情報のフォローを解除しました。",
+                "Unfollowed lecture information of \"{}\": \"{}\"{}.",
+                id, title, format_excludes(&excludes, lang)
+            ),
+            Some(Follow::Pattern { title, lecturer: Some(lecturer), excludes }) => respondln!(
+                resp, lang,
+                "ID {}（{} [{}]{}）の情報のフォローを解除しました。",
+                "Unfollowed lecture information of \"{}\": \"{}\" by {}{}.",
+                id, title, lecturer, format_excludes(&excludes, lang)
             ),
-            Some(Follow::Pattern { title, lecturer: Some(lecturer) }) => respondln!(
+            Some(Follow::Regex { pattern }) => respondln!(
                 resp, lang,
-                "ID {}（{} [{}]）の情報のフォローを解除しました。",
-                "Unfollowed lecture information of \"{}\": \"{}\" by {}.",
-                id, title, lecturer
+                "ID {}（/{}/）の情報のフォローを解除しました。",
+                "Unfollowed lecture information of \"{}\": /{}/.",
+                id, pattern
+            ),
+            Some(Follow::Filter { dept, kind, weekday, periods }) => respondln!(
+                resp, lang,
+                "ID {}（{}）の情報のフォローを解除しました。",
+                "Unfollowed lecture information of \"{}\": {}.",
+                id, format_filter(&dept, &kind, weekday, &periods)
             ),
             Some(Follow::TweetId(tweet_id)) => respondln!(
                 resp, lang,
-                "ID {}（https://twitter.com/{}/status/{}）の情報のフォローを解除しました。",
-                "Unfollowed lecture information of \"{}\"(https://twitter.com/{}/status/{})",
-                id, recipient_screen_name, tweet_id
+                "ID {}（{}）の情報のフォローを解除しました。",
+                "Unfollowed lecture information of \"{}\"({})",
+                id, tweet_url(recipient_screen_name, tweet_id)
             ),
             None => respondln!(
                 resp, lang,
@@ -230,19 +676,26 @@ fn list(resp: &mut String, sender: &UserInfo, lang: &str, recipient_screen_name:
 
         for (id, &FollowEntry(ref follow, via)) in &sender.following {
             match *follow {
-                Follow::Pattern { ref title, lecturer: None } => respondln!(
+                Follow::Pattern { ref title, lecturer: None, ref excludes } => respondln!(
+                    resp, lang,
+                    "・{}{}（ID：{}；{}）", "* \"{}\"{} (ID: {}; {})", title, format_excludes(excludes, lang), id, via
+                ),
+                Follow::Pattern { ref title, lecturer: Some(ref lecturer), ref excludes } => respondln!(
+                    resp, lang,
+                    "・{}［{}］{}（ID：{}；{}）", "* \"{}\" by {}{} (ID: {}; {})",
+                    title, lecturer, format_excludes(excludes, lang), id, via
+                ),
+                Follow::Regex { ref pattern } => respondln!(
                     resp, lang,
-                    "・{}（ID：{}；{}）", "* \"{}\" (ID: {}; {})", title, id, via
+                    "・/{}/（ID：{}；{}）", "* /{}/ (ID: {}; {})", pattern, id, via
                 ),
-                Follow::Pattern { ref title, lecturer: Some(ref lecturer) } => respondln!(
+                Follow::Filter { ref dept, ref kind, weekday, ref periods } => respondln!(
                     resp, lang,
-                    "・{}［{}］（ID：{}；{}）", "* \"{}\" by {} (ID: {}; {})", title, lecturer, id, via
+                    "・{}（ID：{}；{}）", "* {} (ID: {}; {})", format_filter(dept, kind, weekday, periods), id, via
                 ),
                 Follow::TweetId(tweet_id) => respondln!(
                     resp, lang,
-                    "・https://twitter.com/{}/status/{}（ID: {}；{}）",
-                    "* https://twitter.com/{}/status/{} (ID: {}; {})",
-                    recipient_screen_name, tweet_id, id, via
+                    "・{}（ID: {}；{}）", "* {} (ID: {}; {})", tweet_url(recipient_screen_name, tweet_id), id, via
                 ),
             }
         }
@@ -250,3 +703,361 @@ fn list(resp: &mut String, sender: &UserInfo, lang: &str, recipient_screen_name:
 
     Ok(())
 }
+
+/// An invalid argument of the `remind` command.
+#[derive(Debug, Eq, PartialEq)]
+enum RemindArgError<'a> {
+    /// An argument that is not understood.
+    Invalid(&'a str),
+    /// The days before the date are specified without the time.
+    NoTime,
+}
+
+/// Parses the arguments of the `remind` command like `前日 20:00`, `7:00` or `60分前` into the preference, where
+/// `None` (given as `default`) means `Settings::reminder`.
+fn parse_remind<'a>(args: &[&'a str]) -> ::std::result::Result<Option<RemindPreference>, RemindArgError<'a>> {
+    use chrono::NaiveTime;
+
+    /// Parses a number followed by one of `suffixes`, e.g. `60m`.
+    fn number_with_suffix(s: &str, suffixes: &[&str]) -> Option<u32> {
+        suffixes.iter()
+            .filter(|&&suffix| s.ends_with(suffix))
+            .filter_map(|suffix| s[..s.len()-suffix.len()].parse().ok())
+            .next()
+    }
+
+    let (mut days_before, mut time, mut minutes, mut reset) = (0, None, None, false);
+
+    for &a in args {
+        if a == "default" {
+            reset = true;
+        } else if a == "eve" || a == "前日" {
+            days_before = 1;
+        } else if let Some(n) = number_with_suffix(a, &["d", "日前"]) {
+            days_before = n;
+        } else if let Some(n) = number_with_suffix(a, &["m", "分前"]) {
+            minutes = Some(n);
+        } else if let Ok(t) = NaiveTime::parse_from_str(a, "%H:%M") {
+            time = Some(t);
+        } else {
+            return Err(RemindArgError::Invalid(a));
+        }
+    }
+
+    if reset {
+        Ok(None)
+    } else if let Some(m) = minutes {
+        Ok(Some(RemindPreference::BeforePeriod(m)))
+    } else if let Some(t) = time {
+        Ok(Some(RemindPreference::At(Reminder { days_before: days_before, time: t })))
+    } else {
+        Err(RemindArgError::NoTime)
+    }
+}
+
+fn remind<'a, I: Iterator<Item=&'a str>>(tokens: I, resp: &mut String, sender: &mut UserInfo, lang: &str,
+    settings: &Settings) -> Result<()>
+{
+    let args = tokens.collect::<Vec<_>>();
+
+    if args.is_empty() {
+        let current = sender.remind.unwrap_or(RemindPreference::At(settings.reminder));
+        respondln!(
+            resp, lang,
+            "リマインダーは{}に送信されます。", "Reminders are sent {}.",
+            describe_remind(&current, lang)
+        );
+        return Ok(());
+    }
+
+    match parse_remind(&args) {
+        Ok(pref) => sender.remind = pref,
+        Err(RemindArgError::Invalid(a)) => {
+            respondln!(
+                resp, lang,
+                "不正な指定です：{}（例：`remind 前日 20:00`、`remind 7:00`、`remind 60分前`）",
+                "Invalid argument: {} (e.g. `remind eve 20:00`, `remind 7:00`, `remind 60m`)",
+                a
+            );
+            return Ok(());
+        },
+        Err(RemindArgError::NoTime) => {
+            respondln!(
+                resp, lang,
+                "時刻を指定してください（例：`remind 前日 20:00`）", "Specify the time (e.g. `remind eve 20:00`)."
+            );
+            return Ok(());
+        },
+    }
+
+    let current = sender.remind.unwrap_or(RemindPreference::At(settings.reminder));
+    respondln!(
+        resp, lang,
+        "リマインダーを{}に送信するように設定しました。", "Reminders will be sent {}.",
+        describe_remind(&current, lang)
+    );
+
+    Ok(())
+}
+
+fn digest<'a, I: Iterator<Item=&'a str>>(mut tokens: I, resp: &mut String, sender: &mut UserInfo, lang: &str)
+    -> Result<()>
+{
+    use chrono::NaiveTime;
+
+    match tokens.next() {
+        None => match sender.digest_time {
+            Some(t) => respondln!(
+                resp, lang,
+                "新着情報は毎日{}にまとめて送信されます。", "New information is sent together at {} every day.",
+                t.format("%-H:%M")
+            ),
+            None => respondln!(
+                resp, lang,
+                "ダイジェストは無効です（例：`digest 7:00`）。", "The digest is disabled (e.g. `digest 7:00`)."
+            ),
+        },
+        Some("off") => {
+            sender.digest_time = None;
+            respondln!(
+                resp, lang,
+                "ダイジェストを無効にしました。未送信の情報はまもなく送信され、新着情報は都度通知されます。",
+                "Disabled the digest. Pending information will be sent shortly and new one each time."
+            );
+        },
+        Some(a) => match NaiveTime::parse_from_str(a, "%H:%M") {
+            Ok(t) => {
+                sender.digest_time = Some(t);
+                respondln!(
+                    resp, lang,
+                    "新着情報を毎日{}にまとめて送信します。", "New information will be sent together at {} every day.",
+                    t.format("%-H:%M")
+                );
+            },
+            Err(_) => respondln!(
+                resp, lang,
+                "不正な時刻です：{}（例：`digest 7:00`）", "Invalid time: {} (e.g. `digest 7:00`)", a
+            ),
+        },
+    }
+
+    Ok(())
+}
+
+/// Describes when reminders are sent, e.g. `前日20:00` or `at 20:00 on the day before`.
+fn describe_remind(pref: &RemindPreference, lang: &str) -> String {
+    let en = lang.starts_with("en");
+
+    match *pref {
+        RemindPreference::At(Reminder { days_before, time }) => {
+            let time = time.format("%-H:%M");
+            match (days_before, en) {
+                (0, false) => format!("当日{}", time),
+                (1, false) => format!("前日{}", time),
+                (n, false) => format!("{}日前の{}", n, time),
+                (0, true) => format!("at {} on the day", time),
+                (1, true) => format!("at {} on the day before", time),
+                (n, true) => format!("at {} {} days before", time, n),
+            }
+        },
+        RemindPreference::BeforePeriod(m) if en => format!("{} minutes before the lecture starts", m),
+        RemindPreference::BeforePeriod(m) => format!("講義開始の{}分前", m),
+    }
+}
+
+fn list_departments(resp: &mut String, departments: &[Department], lang: &str) -> Result<()> {
+    if departments.is_empty() {
+        respondln!(resp, lang,
+            "自動検出された学部はありません。", "No departments have been discovered."
+        );
+    } else {
+        for d in departments {
+            match d.tab.suffix {
+                Some(ref suffix) => respondln!(resp, lang, "・{}（{}）：{}", "* {} ({}): {}", d.tab.name, suffix, d.url),
+                None => respondln!(resp, lang, "・{}：{}", "* {}: {}", d.tab.name, d.url),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_term_test() {
+        macro_rules! test_eq {
+            ($src:expr, $expect:expr) => {{
+                assert_eq!(filter_term($src), $expect);
+            }};
+        }
+
+        test_eq!("in:工学部", Some(("in", "工学部")));
+        test_eq!("kind:休講", Some(("kind", "休講")));
+        test_eq!("on:火", Some(("on", "火")));
+        test_eq!("period:3-4", Some(("period", "3-4")));
+        test_eq!("period:", None);
+        test_eq!("at:工学部", None);
+        test_eq!("線形代数", None);
+    }
+
+    #[test]
+    fn parse_weekday_test() {
+        macro_rules! test_eq {
+            ($src:expr, $expect:expr) => {{
+                assert_eq!(parse_weekday($src), $expect);
+            }};
+        }
+
+        test_eq!("月", Some(0));
+        test_eq!("火曜", Some(1));
+        test_eq!("水曜日", Some(2));
+        test_eq!("Thu", Some(3));
+        test_eq!("friday", Some(4));
+        test_eq!("SAT", Some(5));
+        test_eq!("日", Some(6));
+        test_eq!("祝", None);
+        test_eq!("", None);
+    }
+
+    #[test]
+    fn parse_kind_test() {
+        macro_rules! test_eq {
+            ($src:expr, $expect:expr) => {{
+                assert_eq!(parse_kind($src), $expect);
+            }};
+        }
+
+        test_eq!("休講", Some(KyukoKind::Cancellation));
+        test_eq!("【補講】", Some(KyukoKind::MakeUp));
+        test_eq!("cancellation", Some(KyukoKind::Cancellation));
+        test_eq!("make-up class", Some(KyukoKind::MakeUp));
+        test_eq!("MakeUp", Some(KyukoKind::MakeUp));
+        test_eq!("room-change", Some(KyukoKind::RoomChange));
+        test_eq!("TimeChange", Some(KyukoKind::TimeChange));
+        test_eq!("misc", Some(KyukoKind::Misc));
+        test_eq!("その他", Some(KyukoKind::Misc));
+        test_eq!("集中講義", None);
+    }
+
+    #[test]
+    fn page_arg_test() {
+        macro_rules! test_eq {
+            ($src:expr, $terms:expr, $page:expr) => {{
+                let (terms, page) = page_arg($src.split(' ').filter(|s| !s.is_empty()));
+                assert_eq!(terms, &$terms as &[&str]);
+                assert_eq!(page, $page);
+            }};
+        }
+
+        test_eq!("線形代数", ["線形代数"], 1);
+        test_eq!("線形代数 p2", ["線形代数"], 2);
+        test_eq!("線形代数 山田 p12", ["線形代数", "山田"], 12);
+        test_eq!("p2 線形代数", ["p2", "線形代数"], 1);
+        test_eq!("線形代数 p0", ["線形代数"], 1);
+        test_eq!("線形代数 p99", ["線形代数"], 99);
+        test_eq!("physics", ["physics"], 1);
+        test_eq!("p", ["p"], 1);
+        test_eq!("p2", [], 2);
+        test_eq!("", [], 1);
+    }
+
+    #[test]
+    fn parse_date_test() {
+        let ymd = NaiveDate::from_ymd;
+        let today = ymd(2017, 6, 1);
+
+        macro_rules! test_eq {
+            ($src:expr, $expect:expr) => {{
+                assert_eq!(parse_date($src, today), $expect);
+            }};
+        }
+
+        test_eq!("6/12", Some(ymd(2017, 6, 12)));
+        test_eq!("6月12日", Some(ymd(2017, 6, 12)));
+        test_eq!("6月12", Some(ymd(2017, 6, 12)));
+        test_eq!("2017-06-12", Some(ymd(2017, 6, 12)));
+        test_eq!("2018/01/05", Some(ymd(2018, 1, 5)));
+        test_eq!("6/31", None);
+        test_eq!("12日", None);
+        test_eq!("6/12/1", None);
+        test_eq!("search", None);
+
+        // Dates long before `today` are regarded as those of the next year.
+        assert_eq!(parse_date("1/10", ymd(2016, 12, 1)), Some(ymd(2017, 1, 10)));
+    }
+
+    #[test]
+    fn respond_entries_test() {
+        use config::tests::kyuko;
+
+        let ks = (0..5).map(|i| Kyuko { title: format!("講義{}", i), ..kyuko() }).collect::<Vec<_>>();
+        let respond = |ks: &[Kyuko], page, limit| {
+            let mut resp = String::new();
+            let entries = ks.iter().map(|k| ("教養教育", "1", k)).collect();
+            respond_entries(&mut resp, "ja", "bot", entries, page, "search 講義", limit).unwrap();
+            resp
+        };
+        // The length of each line including the line break.
+        let line = "・6/12 3講時 教養教育／休講：講義0 [山田] https://twitter.com/bot/status/1\n".chars().count();
+        // The length of the response excluding the lines.
+        let margin = 40 + "search 講義".chars().count();
+
+        // Two lines per page.
+        let limit = margin + line * 2 + line / 2;
+        let resp = respond(&ks, 1, limit);
+        assert!(resp.starts_with("5件（1/3ページ）\n"), "{}", resp);
+        assert!(resp.contains("講義0") && resp.contains("講義1") && !resp.contains("講義2"), "{}", resp);
+        assert!(resp.ends_with("続き：`search 講義 p2`\n"), "{}", resp);
+        assert!(resp.chars().count() <= limit);
+
+        let resp = respond(&ks, 2, limit);
+        assert!(resp.starts_with("5件（2/3ページ）\n"), "{}", resp);
+        assert!(resp.contains("講義2") && resp.contains("講義3"), "{}", resp);
+
+        // The last page is shown for a page beyond it.
+        let resp = respond(&ks, 99, limit);
+        assert!(resp.starts_with("5件（3/3ページ）\n"), "{}", resp);
+        assert!(resp.contains("講義4") && !resp.contains("続き"), "{}", resp);
+
+        // A line longer than the room still makes up a page by itself.
+        let resp = respond(&ks, 1, margin + line / 2);
+        assert!(resp.starts_with("5件（1/5ページ）\n"), "{}", resp);
+        assert!(resp.contains("講義0") && !resp.contains("講義1"), "{}", resp);
+
+        assert_eq!(respond(&ks, 1, 10000).lines().count(), 6);
+        assert_eq!(respond(&[], 1, 10000), "該当する情報はありません。\n");
+    }
+
+    #[test]
+    fn parse_remind_test() {
+        use chrono::NaiveTime;
+        use iba_kyuko_bot::normalize::normalize;
+
+        fn at(days_before: u32, h: u32, m: u32)
+            -> ::std::result::Result<Option<RemindPreference>, RemindArgError<'static>>
+        {
+            Ok(Some(RemindPreference::At(Reminder { days_before: days_before, time: NaiveTime::from_hms(h, m, 0) })))
+        }
+
+        assert_eq!(parse_remind(&["eve", "20:00"]), at(1, 20, 0));
+        assert_eq!(parse_remind(&["前日", "20:00"]), at(1, 20, 0));
+        assert_eq!(parse_remind(&["7:00"]), at(0, 7, 0));
+        assert_eq!(parse_remind(&["2d", "8:30"]), at(2, 8, 30));
+        assert_eq!(parse_remind(&["3日前", "21:15"]), at(3, 21, 15));
+        assert_eq!(parse_remind(&["60m"]), Ok(Some(RemindPreference::BeforePeriod(60))));
+        assert_eq!(parse_remind(&["30分前"]), Ok(Some(RemindPreference::BeforePeriod(30))));
+        assert_eq!(parse_remind(&["default"]), Ok(None));
+
+        assert_eq!(parse_remind(&["前日"]), Err(RemindArgError::NoTime));
+        assert_eq!(parse_remind(&["tomorrow"]), Err(RemindArgError::Invalid("tomorrow")));
+        assert_eq!(parse_remind(&["25:00"]), Err(RemindArgError::Invalid("25:00")));
+
+        // Full-width input is normalized by `message` before being parsed.
+        let normalized = normalize("前日　２０：００");
+        let args = normalized.split(' ').collect::<Vec<_>>();
+        assert_eq!(parse_remind(&args), at(1, 20, 0));
+    }
+}
@@ -1,28 +1,61 @@
 mod message;
+mod digest;
+mod remind;
 
-use chrono::Local;
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime};
 use config::*;
 use egg_mode::direct;
 use egg_mode::user::{self, TwitterUser};
 use egg_mode::tweet::DraftTweet;
 use errors::*;
 use hyper::client::Client;
-use iba_kyuko_bot::Kyuko;
-use schedule::Schedule;
+use iba_kyuko_bot::{Kyuko, KyukoKind, Periods, Tab, Timetable};
+use schedule::{Schedule, UnitSchedule};
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::fs::File;
 use twitter_stream::{DirectMessage, StreamMessage, Tweet, TwitterJsonStream};
 use util::{self, SyncFile};
 
-pub fn run(mut tweeted: SyncFile<Tweeted>, mut users: SyncFile<UserMap>, settings: Settings, archive: File)
-    -> Result<()>
+/// The Japanese names of the days of the week, starting from Monday.
+const WDAYS: [&'static str; 7] = ["月", "火", "水", "木", "金", "土", "日"];
+
+/// The maximum length of an alert to the admins, which is far shorter than the limit of direct messages.
+const ALERT_TEXT_LIMIT: usize = 1000;
+
+/// The number of hours after which an unresolved alert is sent again.
+const ALERT_INTERVAL_HOURS: i64 = 24;
+
+/// A department discovered from the tabs of `Settings::base_url`.
+pub struct Department {
+    pub tab: Tab,
+    /// The absolute URL of the department's page.
+    pub url: String,
+}
+
+/// The state of the daemon that is not persisted.
+#[derive(Default)]
+pub struct State {
+    departments: Vec<Department>,
+    /// The last alert sent to the admins for each URL and when it was sent, to avoid repeating the same alert on
+    /// every crawl.
+    alerts: HashMap<String, (String, NaiveDateTime)>,
+    regexes: RegexCache,
+    /// The screen name of the authenticating user.
+    screen_name: String,
+}
+
+pub fn run(mut tweeted: SyncFile<Tweeted>, mut users: SyncFile<UserMap>, mut digests: SyncFile<Digests>,
+    settings: Settings, archive: File) -> Result<()>
 {
     use egg_mode::{self, service, Response};
     use futures::{Future, Stream};
     use json;
 
-    enum TweetOrDm {
+    enum Event {
+        Update,
+        Tick,
         Dm(DirectMessage),
         Tweet(Tweet),
     }
@@ -36,23 +69,27 @@ pub fn run(mut tweeted: SyncFile<Tweeted>, mut users: SyncFile<UserMap>, setting
 
     let tz = Local;
     let schedule = Schedule::new(&settings.schedule, &tz);
+    // Fires every minute to send reminders.
+    let every_minute = [UnitSchedule::new(vec![], vec![], vec![]).unwrap()];
+    let ticks = Schedule::new(&every_minute, &tz);
 
-    let Response { response: TwitterUser { id, .. }, .. } = egg_mode::verify_tokens(&settings.token.clone().into())
-        .chain_err(|| "failed to retrieve the information of the authenticating user")?;
+    let Response { response: TwitterUser { id, screen_name, .. }, .. } =
+        egg_mode::verify_tokens(&settings.token.clone().into())
+            .chain_err(|| "failed to retrieve the information of the authenticating user")?;
 
     let messages = TwitterJsonStream::user(&settings.token)
         .chain_err(|| "failed to connect to User Stream")?
         .then(|r| r.chain_err(|| "an error occured while listening on User Stream"))
         .filter_map(|json| match json::from_str(&json) {
             Ok(StreamMessage::Tweet(t)) => if t.in_reply_to_user_id == Some(id) {
-                Some(TweetOrDm::Tweet(*t))
+                Some(Event::Tweet(*t))
             } else {
                 // XXX: This clause can be removed after RFC 0107 was implemented.
                 // cf. https://github.com/rust-lang/rfcs/blob/master/text/0107-pattern-guards-with-bind-by-move.md
                 None
             },
             Ok(StreamMessage::DirectMessage(dm)) => if dm.recipient_id == id {
-                Some(TweetOrDm::Dm(*dm))
+                Some(Event::Dm(*dm))
             } else {
                 None
             },
@@ -60,23 +97,25 @@ pub fn run(mut tweeted: SyncFile<Tweeted>, mut users: SyncFile<UserMap>, setting
         });
 
     let client = Client::new();
+    let mut state = State {
+        screen_name: screen_name,
+        ..State::default()
+    };
 
-    let future = schedule.merge(messages).for_each(|merged| {
-        use futures::stream::MergedItem::*;
-        match merged {
-            First(()) => update(&mut tweeted, &users, &settings, &archive, &client, url_len),
-            Second(msg) => match msg {
-                TweetOrDm::Tweet(t) => reply(t, &mut tweeted, &mut users, &settings, url_len),
-                TweetOrDm::Dm(dm) => direct_message(dm, &mut tweeted, &mut users, &settings, dm_text_limit),
-            },
-            Both((), msg) => {
-                match msg {
-                    TweetOrDm::Tweet(t) => reply(t, &mut tweeted, &mut users, &settings, url_len)?,
-                    TweetOrDm::Dm(dm) => direct_message(dm, &mut tweeted, &mut users, &settings, dm_text_limit)?,
-                }
-                update(&mut tweeted, &users, &settings, &archive, &client, url_len)
-            },
-        }
+    let events = schedule.map(|()| Event::Update)
+        .select(ticks.map(|()| Event::Tick))
+        .select(messages);
+
+    let future = events.for_each(|event| match event {
+        Event::Update =>
+            update(&mut tweeted, &users, &mut digests, &settings, &archive, &client, url_len, &mut state),
+        Event::Tick => {
+            remind::remind(&mut users, &tweeted, &settings, &state.screen_name)?;
+            digest::send(&users, &mut digests, &tweeted, &settings, &state.screen_name, dm_text_limit)
+        },
+        Event::Tweet(t) => reply(t, &mut tweeted, &mut users, &mut digests, &settings, &state, url_len),
+        Event::Dm(dm) =>
+            direct_message(dm, &mut tweeted, &mut users, &mut digests, &settings, &state, dm_text_limit),
     });
 
     info!("started");
@@ -84,11 +123,12 @@ pub fn run(mut tweeted: SyncFile<Tweeted>, mut users: SyncFile<UserMap>, setting
     future.wait()
 }
 
-fn update(tweeted: &mut SyncFile<Tweeted>, users: &SyncFile<UserMap>, settings: &Settings, archive: &File,
-    client: &Client, url_len: (i32, i32)) -> Result<()>
+fn update(tweeted: &mut SyncFile<Tweeted>, users: &SyncFile<UserMap>, digests: &mut SyncFile<Digests>,
+    settings: &Settings, archive: &File, client: &Client, url_len: (i32, i32), state: &mut State) -> Result<()>
 {
-    fn fetch(url: &str, client: &Client, user_agent: &str, keep_alive: bool) -> Result<String> {
-        use hyper::header::{Connection, UserAgent};
+    /// Returns the response body and the value of the `Content-Type` header.
+    fn fetch(url: &str, client: &Client, user_agent: &str, keep_alive: bool) -> Result<(Vec<u8>, Option<String>)> {
+        use hyper::header::{Connection, ContentType, UserAgent};
         use hyper::status::StatusCode;
         use std::io::Read;
 
@@ -103,117 +143,329 @@ fn update(tweeted: &mut SyncFile<Tweeted>, users: &SyncFile<UserMap>, settings:
             return Err(res.status.to_string().into());
         }
 
-        let mut body = String::new();
-        res.read_to_string(&mut body).chain_err(|| "failed to read the response body")?;
+        let content_type = res.headers.get::<ContentType>().map(ToString::to_string);
+
+        let mut body = Vec::new();
+        res.read_to_end(&mut body).chain_err(|| "failed to read the response body")?;
 
-        Ok(body)
+        Ok((body, content_type))
     }
 
-    fn remove_withdrawn(dept: &str, old: &mut HashMap<String, Kyuko>, new: &[Kyuko], archive: &File) -> Result<()> {
-        use std::io::{self, Write};
+    fn discover(base: &str, tabs: Vec<Tab>) -> Result<Vec<Department>> {
+        use hyper::Url;
 
-        fn write_archive(mut archive: &File, dept: &str, tweet_id: String, k: Kyuko) -> io::Result<()> {
-            match k.remarks {
-                Some(remarks) => writeln!(archive, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-                    tweet_id, dept, k.kind, k.date, k.periods, k.title, k.lecturer, remarks),
-                None              => writeln!(archive, "{}\t{}\t{}\t{}\t{}\t{}\t{}",
-                    tweet_id, dept, k.kind, k.date, k.periods, k.title, k.lecturer),
+        let base = Url::parse(base).chain_err(|| format!("invalid base URL: {}", base))?;
+
+        tabs.into_iter().map(|tab| {
+            let url = base.join(&tab.href).chain_err(|| format!("invalid department URL: {}", tab.href))?;
+            Ok(Department {
+                tab: tab,
+                url: url.as_str().to_owned(),
+            })
+        }).collect()
+    }
+
+    fn write_archive(mut archive: &File, dept: &str, tweet_id: &str, k: &Kyuko) -> Result<()> {
+        use std::io::Write;
+
+        let result = match k.remarks {
+            Some(ref remarks) => writeln!(archive, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                tweet_id, dept, k.kind, k.date, k.periods, k.title, k.lecturer, remarks),
+            None              => writeln!(archive, "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                tweet_id, dept, k.kind, k.date, k.periods, k.title, k.lecturer),
+        };
+
+        result.chain_err(|| "failed to write to the archive file")
+    }
+
+    info!("started crawling");
+
+    let mut urls = settings.urls.clone();
+    // The page of `base_url` along with the URL of its selected department, whose page need not be fetched again.
+    let mut base_page = None;
+
+    if let Some(ref base) = settings.base_url {
+        info!("fetching {}", base);
+        let (html, content_type) = fetch(base, client, &settings.user_agent, true)
+            .chain_err(|| format!("failed to fetch {}", base))?;
+        let page = ::iba_kyuko_bot::scrape_bytes(&html, content_type.as_ref().map(String::as_str))
+            .chain_err(|| format!("failed to scrape {}", base))?;
+        let found = discover(base, page.tabs.clone())?;
+
+        for d in &found {
+            if !state.departments.iter().any(|old| old.url == d.url) {
+                info!("discovered a department: {} ({})", d.tab.name, d.url);
             }
         }
-
-        // TODO: explore more efficient way.
-        // cf. Map::retain · Issue #1338 · rust-lang/rfcs https://github.com/rust-lang/rfcs/issues/1338
-        let mut ret = HashMap::with_capacity(old.len());
-        for (tweet_id, k) in old.drain() {
-            if new.contains(&k) {
-                ret.insert(tweet_id, k);
-            } else {
-                write_archive(archive, dept, tweet_id, k).chain_err(|| "failed to write to the archive file")?;
+        for d in &state.departments {
+            if !found.iter().any(|new| new.url == d.url) {
+                warn!("a department is no longer listed: {} ({})", d.tab.name, d.url);
             }
         }
 
-        *old = ret;
+        state.departments = found;
+
+        for d in &state.departments {
+            if !urls.contains(&d.url) {
+                urls.push(d.url.clone());
+            }
+        }
 
-        Ok(())
+        base_page = state.departments.iter().find(|d| d.tab.selected).map(|d| (d.url.clone(), page));
     }
 
-    info!("started crawling");
+    let to_fetch = urls.iter()
+        .filter(|&url| base_page.as_ref().map_or(true, |&(ref base_url, _)| base_url != url))
+        .collect::<Vec<_>>();
 
     // Eagarly evaluate HTTP connections to prevent disconnection from the server.
     let mut buf = Vec::new();
-    for (i, url) in settings.urls.iter().enumerate() {
+    for (i, url) in to_fetch.iter().enumerate() {
         info!("fetching {}", url);
-        let html = fetch(url, client, &settings.user_agent, i+1 < settings.urls.len())
+        let html = fetch(url, client, &settings.user_agent, i+1 < to_fetch.len())
             .chain_err(|| format!("failed to fetch {}", url))?;
         buf.push(html);
     }
+    let mut buf = buf.into_iter();
+
+    for url in &urls {
+        let page = if base_page.as_ref().map_or(false, |&(ref base_url, _)| base_url == url) {
+            base_page.take().unwrap().1
+        } else {
+            // `buf` has the pages of `to_fetch`, which are in the same order as `urls`.
+            let (html, content_type) = buf.next().unwrap();
+            ::iba_kyuko_bot::scrape_bytes(&html, content_type.as_ref().map(String::as_str))
+                .chain_err(|| format!("failed to scrape {}", url))?
+        };
+        let dept = page.department().name.clone();
+
+        let mut problems = Vec::new();
+
+        if !page.failures.is_empty() {
+            warn!("failed to parse {} row(s) of {}", page.failures.len(), url);
+            problems.push(format!(
+                "failed to parse {} of {} rows", page.failures.len(), page.failures.len() + page.kyukos.len()
+            ));
+            for f in &page.failures {
+                warn!("row {}: failed to parse the {}: {}\nDOM tree: {}", f.index, f.field, f.message, f.html);
+                problems.push(format!("row {}: {}: {}", f.index, f.field, f.message));
+            }
+        }
+
+        for f in &page.fallbacks {
+            info!("row {} of {}: applied a fallback to the {}: {:?}", f.index, url, f.field, f.fallback);
+        }
+
+        let mut archive_withdrawn = page.failures.len() <= settings.max_row_failures;
 
-    for (url, html) in settings.urls.iter().zip(buf.drain(..)) {
-        let (dept, mut kyukos) = ::iba_kyuko_bot::scrape(html).chain_err(|| format!("failed to scrape {}", url))?;
+        if let Some(n) = page.count_mismatch() {
+            // The layout of the website may have changed, in which case every information would look withdrawn.
+            warn!("{} claims to have {} items but {} rows were found", url, n, page.kyukos.len() + page.failures.len());
+            problems.push(format!(
+                "the page claims to have {} items but {} rows were found", n, page.kyukos.len() + page.failures.len()
+            ));
+            archive_withdrawn = false;
+        }
+
+        if !archive_withdrawn {
+            warn!("skipping archiving withdrawn information of {}", url);
+            problems.push("skipped archiving withdrawn information".to_owned());
+        }
+
+        alert_admins(url, &problems, state, settings);
+
+        let kyukos = page.kyukos;
 
         {
             let mut tweeted_kyukos = tweeted.entry(dept.clone()).or_insert_with(HashMap::new);
-            remove_withdrawn(&dept, &mut tweeted_kyukos, &kyukos, archive)?;
 
-            // Retain information to post.
-            kyukos.retain(|k| !tweeted_kyukos.values().any(|c| c == k));
+            let (added, withdrawn, modified) = {
+                let d = ::iba_kyuko_bot::diff(tweeted_kyukos.iter().map(|(id, k)| (id.clone(), k)), &kyukos);
+                (
+                    d.added.into_iter().cloned().collect::<Vec<_>>(),
+                    d.withdrawn.into_iter().map(|(id, _)| id).collect::<Vec<_>>(),
+                    d.modified.into_iter().map(|m| (m.key, m.old.clone(), m.new.clone(), m.changes))
+                        .collect::<Vec<_>>(),
+                )
+            };
+
+            if archive_withdrawn {
+                let today = Local::today().naive_local();
+
+                for id in withdrawn {
+                    let k = tweeted_kyukos.remove(&id).unwrap();
+                    write_archive(archive, &dept, &id, &k)?;
+                    info!("archived withdrawn information: status_id = {}", id);
+
+                    // Past information disappears from the website as a matter of course.
+                    if k.until.unwrap_or(k.date) >= today {
+                        let text = format_withdrawal(&dept, &k, &id, &state.screen_name);
+                        let to_notify = followers(users, &dept, &[&k], id.parse().ok(), settings, &state.regexes);
+                        notify_followers(users, &to_notify, &text, settings)?;
+                    }
+                }
+            }
+
+            for (id, old, k, changes) in modified {
+                // Information stored before the remarks were extracted in full is not a correction to announce.
+                if old.has_truncated_remarks(&k) {
+                    info!("completed the remarks stored by an older version: status_id = {}", id);
+                    tweeted_kyukos.insert(id, k);
+                    continue;
+                }
 
-            for k in kyukos.drain(..) {
-                let text = format_tweet(&dept, &k, url, url_len);
+                let header = format!(
+                    "【訂正：{}】\n", changes.iter().map(|f| f.ja()).collect::<Vec<_>>().join("・")
+                );
+                let text = format_tweet(&header, &dept, &k, url, url_len, settings.timetable(&dept));
+                let status_id: u64 = id.parse().chain_err(|| format!("invalid status id: {}", id))?;
 
-                // Post the information to Twitter:
-                let id = DraftTweet::new(&text)
+                // Reply to the original Tweet so that its followers can see the correction:
+                match DraftTweet::new(&text).in_reply_to(status_id).send(&settings.token.clone().into()) {
+                    Ok(r) => info!("successfully tweeted a correction: status_id = {}\n{}", r.id, text),
+                    Err(e) => warn!("failed to post a correction {:?}\ncaused by: {:?}", text, e),
+                }
+
+                let to_notify = followers(users, &dept, &[&old, &k], Some(status_id), settings, &state.regexes);
+                notify_followers(users, &to_notify, &text, settings)?;
+
+                // Keep the original ID so that `Follow::TweetId`s remain valid.
+                tweeted_kyukos.insert(id, k);
+            }
+
+            // Tweet cancellations first so that make-up classes added at the same time can be linked to them.
+            let mut added = added;
+            added.sort_by_key(|k| k.kind != KyukoKind::Cancellation);
+
+            for k in added {
+                let text = format_tweet("", &dept, &k, url, url_len, settings.timetable(&dept));
+
+                let cancellation = match ::iba_kyuko_bot::find_cancellation(&k, tweeted_kyukos.iter()) {
+                    Some((id, _)) => Some(id.parse::<u64>().chain_err(|| format!("invalid status id: {}", id))?),
+                    None => None,
+                };
+
+                // Post the information to Twitter, as a reply to the cancelled class if it is a make-up class:
+                let mut draft = DraftTweet::new(&text);
+                if let Some(c) = cancellation {
+                    draft = draft.in_reply_to(c);
+                }
+                let id = draft
                     .send(&settings.token.clone().into())
                     .chain_err(|| format!("failed to post a Tweet: {:?}", text))?
                     .id;
                 info!("successfully tweeted: status_id = {}\n{}", id, text);
 
-                // Send notifications to users following the information:
-                for (user_id, via) in users.iter().filter_map(|(user_id, u)| {
-                    if let Some(&FollowEntry(_, via)) = u.following.values()
-                        .find(|&&FollowEntry(ref f, _)| f.matches(&k))
-                    {
-                        Some((user_id, via))
+                // Send notifications to users following the information or the cancelled class, or collect them into
+                // the digests of users who have enabled it:
+                let mut immediate = Vec::new();
+                for (user_id, via) in followers(users, &dept, &[&k], cancellation, settings, &state.regexes) {
+                    if users.get(&user_id).map_or(false, |u| u.digest_time.is_some()) {
+                        digests.entry(user_id).or_insert_with(Digest::default).pending.push(id);
                     } else {
-                        None
-                    }
-                }) {
-                    let user_id: u64 = user_id.parse()
-                        .chain_err(|| format!("invalid user ID in {:?}", users.file_name()))?;
-                    match via {
-                        MessageMethod::Dm => {
-                            if let Err(e) = direct::send(user_id, &text, &settings.token.clone().into()) {
-                                warn!("failed to send a direct message {:?}\ncaused by: {:?}", text, e);
-                            }
-                        },
-                        MessageMethod::Reply => {
-                            match user::show(user_id, &settings.token.clone().into()) {
-                                Ok(user) => {
-                                    let text = format!("@{} {}", user.screen_name, text);
-                                    if let Err(e) = DraftTweet::new(&text).send(&settings.token.clone().into()) {
-                                        warn!("failed to send a reply {:?}\ncaused by: {:?}", text, e);
-                                    }
-                                },
-                                Err(e) => warn!(
-                                    "failed to retrieve the user information of {}\ncaused by {:?}", user_id, e
-                                ),
-                            }
-                        }
+                        immediate.push((user_id, via));
                     }
                 }
+                notify_followers(users, &immediate, &text, settings)?;
 
                 tweeted_kyukos.insert(id.to_string(), k);
             }
         }
 
         tweeted.commit()?;
+        digests.commit()?;
     }
 
     Ok(())
 }
 
+/// Returns the users who follow any of `ks` of the department `dept` by a pattern, or follow the Tweet `tweet_id`,
+/// along with how to notify them.
+fn followers(users: &UserMap, dept: &str, ks: &[&Kyuko], tweet_id: Option<u64>, settings: &Settings,
+    regexes: &RegexCache) -> Vec<(String, MessageMethod)>
+{
+    users.iter().filter_map(|(user_id, u)| {
+        u.following.values()
+            .find(|&&FollowEntry(ref f, _)| {
+                ks.iter().any(|k| f.matches(dept, k, settings.matching, regexes))
+                    || tweet_id.map_or(false, |id| *f == Follow::TweetId(id))
+            })
+            .map(|&FollowEntry(_, via)| (user_id.clone(), via))
+    }).collect()
+}
+
+/// Sends `text` to each of `followers`.
+fn notify_followers(users: &SyncFile<UserMap>, followers: &[(String, MessageMethod)], text: &str, settings: &Settings)
+    -> Result<()>
+{
+    for &(ref user_id, via) in followers {
+        let user_id: u64 = user_id.parse().chain_err(|| format!("invalid user ID in {:?}", users.file_name()))?;
+        notify(user_id, via, text, settings);
+    }
+
+    Ok(())
+}
+
+/// Formats a notification that `k`, which was posted as the Tweet `tweet_id` by `screen_name`, has been withdrawn
+/// from the website.
+fn format_withdrawal(dept: &str, k: &Kyuko, tweet_id: &str, screen_name: &str) -> String {
+    format!(
+        "【掲示取り下げ】{}\n{}の「{} [{}]」の{}情報が取り下げられました。\n{}",
+        dept, format_when(k), k.title, k.lecturer, k.kind, tweet_url(screen_name, tweet_id)
+    )
+}
+
+/// Sends `text` to the user via `via`, logging failures.
+fn notify(user_id: u64, via: MessageMethod, text: &str, settings: &Settings) {
+    match via {
+        MessageMethod::Dm => {
+            if let Err(e) = direct::send(user_id, text, &settings.token.clone().into()) {
+                warn!("failed to send a direct message {:?}\ncaused by: {:?}", text, e);
+            }
+        },
+        MessageMethod::Reply => {
+            match user::show(user_id, &settings.token.clone().into()) {
+                Ok(user) => {
+                    let text = format!("@{} {}", user.screen_name, text);
+                    if let Err(e) = DraftTweet::new(&text).send(&settings.token.clone().into()) {
+                        warn!("failed to send a reply {:?}\ncaused by: {:?}", text, e);
+                    }
+                },
+                Err(e) => warn!("failed to retrieve the user information of {}\ncaused by {:?}", user_id, e),
+            }
+        },
+    }
+}
+
+/// Sends a direct message describing `problems` of `url` to the admins, unless the same message has already been sent
+/// within `ALERT_INTERVAL_HOURS`, so that problems that persist (e.g. archiving being skipped) are not forgotten.
+fn alert_admins(url: &str, problems: &[String], state: &mut State, settings: &Settings) {
+    if problems.is_empty() {
+        state.alerts.remove(url);
+        return;
+    }
+
+    let mut text = format!("{}\n{}", url, problems.join("\n"));
+    util::shorten(&mut text, ALERT_TEXT_LIMIT);
+
+    let now = Local::now().naive_local();
+    if let Some(&(ref last, sent_at)) = state.alerts.get(url) {
+        if *last == text && now < sent_at + Duration::hours(ALERT_INTERVAL_HOURS) {
+            return;
+        }
+    }
+
+    for &admin in &settings.admins {
+        if let Err(e) = direct::send(admin, &text, &settings.token.clone().into()) {
+            warn!("failed to send a direct message {:?}\ncaused by: {:?}", text, e);
+        }
+    }
+
+    state.alerts.insert(url.to_owned(), (text, now));
+}
+
 fn reply(tweet: Tweet, tweeted: &mut SyncFile<Tweeted>, users: &mut SyncFile<UserMap>,
-    settings: &Settings, url_len: (i32, i32)) -> Result<()>
+    digests: &mut SyncFile<Digests>, settings: &Settings, state: &State, url_len: (i32, i32)) -> Result<()>
 {
     let id = tweet.id;
 
@@ -225,7 +477,7 @@ fn reply(tweet: Tweet, tweeted: &mut SyncFile<Tweeted>, users: &mut SyncFile<Use
 
     let body = message::message(
         MessageMethod::Reply, text, tweet.user, tweet.in_reply_to_screen_name.unwrap(), tweet.in_reply_to_status_id,
-        users, tweeted, settings
+        users, digests, tweeted, settings, state, 140 - (response.len() + 1)
     )?;
 
     if ! body.is_empty() {
@@ -245,10 +497,11 @@ fn reply(tweet: Tweet, tweeted: &mut SyncFile<Tweeted>, users: &mut SyncFile<Use
 }
 
 fn direct_message(dm: DirectMessage, tweeted: &mut SyncFile<Tweeted>, users: &mut SyncFile<UserMap>,
-    settings: &Settings, dm_text_limit: usize) -> Result<()>
+    digests: &mut SyncFile<Digests>, settings: &Settings, state: &State, dm_text_limit: usize) -> Result<()>
 {
     let mut response = message::message(
-        MessageMethod::Dm, &dm.text, dm.sender, dm.recipient.screen_name, None, users, tweeted, settings
+        MessageMethod::Dm, &dm.text, dm.sender, dm.recipient.screen_name, None, users, digests, tweeted, settings,
+        state, dm_text_limit
     )?;
 
     if ! response.is_empty() {
@@ -261,23 +514,85 @@ fn direct_message(dm: DirectMessage, tweeted: &mut SyncFile<Tweeted>, users: &mu
     Ok(())
 }
 
-fn format_tweet(dept: &str, k: &Kyuko, url: &str, url_len: (i32, i32)) -> String {
-    use chrono::Datelike;
+/// Formats a date with its day of the week, e.g. `6月12日（月）`.
+fn format_date(date: NaiveDate) -> String {
+    format!("{}月{}日（{}）", date.month(), date.day(), WDAYS[date.weekday().num_days_from_monday() as usize])
+}
+
+/// Formats periods, appending `講時` to numbered ones, e.g. `3講時` or `午前`.
+fn format_periods(periods: &Periods) -> String {
+    format!("{}{}", periods, if periods.is_numbered() { "講時" } else { "" })
+}
+
+/// Formats the date and periods of `k`, e.g. `6月12日（月）3講時`.
+fn format_when(k: &Kyuko) -> String {
+    format!("{}{}", format_date(k.date), format_periods(&k.periods))
+}
+
+/// Formats a line of a list of information, e.g. `・6月12日（月）3講時 工学部／休講：線形代数 [山田]` followed by `url`
+/// on the next line.
+fn format_entry(dept: &str, k: &Kyuko, url: &str) -> String {
+    format!("・{} {}／{}：{} [{}]\n{}", format_when(k), dept, k.kind, k.title, k.lecturer, url)
+}
+
+/// Returns the URL of the Tweet `tweet_id` posted by `screen_name`.
+fn tweet_url<T: Display>(screen_name: &str, tweet_id: T) -> String {
+    format!("https://twitter.com/{}/status/{}", screen_name, tweet_id)
+}
+
+fn format_tweet(header: &str, dept: &str, k: &Kyuko, url: &str, url_len: (i32, i32), timetable: &Timetable)
+    -> String
+{
     use egg_mode::text;
     use std::fmt::Write;
 
-    const WDAYS: [char; 7] = ['月', '火', '水', '木', '金', '土', '日'];
-
     let mut ret = format!(
         "\
-            {}／{}\n\
+            {}{}／{}\n\
             {} [{}]\n\
-            {}年{}月{}日（{}）{}講時\
+            {}年{}\
         ",
-        escape(dept), escape(k.kind.as_str()), escape(k.title.as_str()), escape(k.lecturer.as_str()),
-        k.date.year(), k.date.month(), k.date.day(), WDAYS[k.date.weekday().num_days_from_monday() as usize], k.periods
+        header, escape(dept), escape(k.kind.ja()), escape(k.title.as_str()), escape(k.lecturer.as_str()),
+        k.date.year(), format_date(k.date)
     );
 
+    if let Some(until) = k.until {
+        write!(ret, "〜{}", format_date(until)).unwrap();
+    }
+
+    ret.push_str(&format_periods(&k.periods));
+
+    // The time of all-day information would be just the time of the whole timetable.
+    if k.periods != Periods::AllDay {
+        if let Some(ranges) = timetable.ranges(&k.periods) {
+            let ranges = ranges.iter()
+                .map(|&(start, end)| format!("{}〜{}", start.format("%-H:%M"), end.format("%-H:%M")))
+                .collect::<Vec<_>>();
+            write!(ret, "（{}）", ranges.join("、")).unwrap();
+        }
+    }
+
+    let details = k.details();
+    if details != Default::default() {
+        ret.push('\n');
+        if let Some(date) = details.date {
+            write!(ret, "［日程：{}", format_date(date)).unwrap();
+            if let Some(ref p) = details.periods {
+                ret.push_str(&format_periods(p));
+            }
+            ret.push('］');
+        }
+        if let Some(ref room) = details.room {
+            write!(ret, "［教室：{}］", escape(room.as_str())).unwrap();
+        }
+        if details.homework {
+            ret.push_str("［課題あり］");
+        }
+        if k.remarks.is_some() {
+            ret.push('\n');
+        }
+    }
+
     if let Some(ref r) = k.remarks {
         write!(ret, "{}\n", escape(r.as_str())).unwrap();
     }
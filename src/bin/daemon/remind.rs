@@ -0,0 +1,151 @@
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime};
+use config::*;
+use errors::*;
+use iba_kyuko_bot::{Kyuko, KyukoKind};
+use std::collections::HashMap;
+use super::{format_when, tweet_url};
+use util::SyncFile;
+
+/// The number of days a `Follow::TweetId` of a cancellation is kept after its last date, since make-up classes are
+/// usually announced after the cancelled date.
+const MAKE_UP_GRACE_DAYS: i64 = 30;
+
+/// Sends reminders of `Follow::TweetId`s whose time (see `UserInfo::remind_time`) has come, once for each Tweet.
+///
+/// A `Follow::TweetId` is kept after its reminder so that the user is still notified of corrections and make-up
+/// classes, and is removed once the information has been withdrawn or its `expiry_date` has passed.
+pub fn remind(users: &mut SyncFile<UserMap>, tweeted: &SyncFile<Tweeted>, settings: &Settings, screen_name: &str)
+    -> Result<()>
+{
+    let now = Local::now().naive_local();
+
+    // (user ID, Tweet ID, the text of the reminder, how to send it)
+    let mut reminders = Vec::new();
+    // (user ID, follow ID)
+    let mut expired = Vec::new();
+
+    for (user_id, u) in users.iter() {
+        for (id, &FollowEntry(ref follow, via)) in &u.following {
+            let tweet_id = if let Follow::TweetId(tweet_id) = *follow { tweet_id } else { continue };
+
+            match find_tweeted(tweeted, tweet_id) {
+                Some((dept, k)) => {
+                    if now.date() > expiry_date(k, &tweeted[dept]) {
+                        expired.push((user_id.clone(), id.clone()));
+                    } else if !u.reminded.contains(&tweet_id) && now.date() <= k.until.unwrap_or(k.date)
+                        && now >= u.remind_time(k, settings.timetable(dept), &settings.reminder)
+                    {
+                        let text = format_reminder(k, now, &tweet_url(screen_name, tweet_id));
+                        reminders.push((user_id.clone(), tweet_id, text, via));
+                    }
+                },
+                None => expired.push((user_id.clone(), id.clone())), // Withdrawn
+            }
+        }
+    }
+
+    if reminders.is_empty() && expired.is_empty() {
+        return Ok(());
+    }
+
+    for (user_id, tweet_id, text, via) in reminders {
+        let uid: u64 = user_id.parse().chain_err(|| format!("invalid user ID in {:?}", users.file_name()))?;
+        super::notify(uid, via, &text, settings);
+        info!("sent a reminder to {}:\n{}", user_id, text);
+        if let Some(u) = users.get_mut(&user_id) {
+            u.reminded.push(tweet_id);
+        }
+    }
+
+    for (user_id, id) in expired {
+        if let Some(u) = users.get_mut(&user_id) {
+            u.following.remove(&id);
+        }
+    }
+
+    // Forget the reminders of Tweets that are no longer followed.
+    for u in users.values_mut() {
+        let following = &u.following;
+        u.reminded.retain(|&tweet_id| following.values().any(|ent| ent.0 == Follow::TweetId(tweet_id)));
+    }
+
+    users.commit()
+}
+
+/// Returns the last date of `k` and the make-up classes for it among `kyukos`.
+fn last_date(k: &Kyuko, kyukos: &HashMap<String, Kyuko>) -> NaiveDate {
+    kyukos.values()
+        .filter(|m| m.is_make_up_of(k))
+        .map(|m| m.until.unwrap_or(m.date))
+        .fold(k.until.unwrap_or(k.date), ::std::cmp::max)
+}
+
+/// Returns the last date on which a `Follow::TweetId` of `k` is kept, which is `MAKE_UP_GRACE_DAYS` after the
+/// `last_date` for cancellations so that their followers are also notified of make-up classes announced later.
+fn expiry_date(k: &Kyuko, kyukos: &HashMap<String, Kyuko>) -> NaiveDate {
+    let last = last_date(k, kyukos);
+    if k.kind == KyukoKind::Cancellation { last + Duration::days(MAKE_UP_GRACE_DAYS) } else { last }
+}
+
+fn format_reminder(k: &Kyuko, now: NaiveDateTime, url: &str) -> String {
+    let day = match k.date.signed_duration_since(now.date()).num_days() {
+        0 => "今日 ",
+        1 => "明日 ",
+        _ => "",
+    };
+
+    format!(
+        "【リマインダー】{}{}の「{} [{}]」は{}です。\n{}", day, format_when(k), k.title, k.lecturer, k.kind, url
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use config::tests::kyuko;
+    use iba_kyuko_bot::{Kyuko, KyukoKind};
+    use std::collections::HashMap;
+    use super::*;
+
+    #[test]
+    fn last_date_test() {
+        let ymd = NaiveDate::from_ymd;
+        let make_up = |date| Kyuko { kind: KyukoKind::MakeUp, date: date, ..kyuko() };
+        let k = kyuko();
+
+        let mut kyukos = HashMap::new();
+        kyukos.insert("1".to_owned(), k.clone());
+        assert_eq!(last_date(&k, &kyukos), ymd(2017, 6, 12));
+
+        let k = Kyuko { until: Some(ymd(2017, 6, 14)), ..kyuko() };
+        assert_eq!(last_date(&k, &kyukos), ymd(2017, 6, 14));
+
+        let k = kyuko();
+        kyukos.insert("2".to_owned(), make_up(ymd(2017, 6, 26)));
+        kyukos.insert("3".to_owned(), make_up(ymd(2017, 6, 19)));
+        // Make-up classes of other lectures, or before the cancelled date, are not counted.
+        kyukos.insert("4".to_owned(), Kyuko { title: "英語".to_owned(), ..make_up(ymd(2017, 7, 3)) });
+        kyukos.insert("5".to_owned(), Kyuko { lecturer: "佐藤".to_owned(), ..make_up(ymd(2017, 7, 3)) });
+        kyukos.insert("6".to_owned(), make_up(ymd(2017, 6, 5)));
+        assert_eq!(last_date(&k, &kyukos), ymd(2017, 6, 26));
+
+        // Only cancellations have make-up classes.
+        let k = Kyuko { kind: KyukoKind::RoomChange, ..kyuko() };
+        assert_eq!(last_date(&k, &kyukos), ymd(2017, 6, 12));
+    }
+
+    #[test]
+    fn expiry_date_test() {
+        let ymd = NaiveDate::from_ymd;
+        let mut kyukos = HashMap::new();
+
+        let k = kyuko();
+        assert_eq!(expiry_date(&k, &kyukos), ymd(2017, 6, 12) + Duration::days(MAKE_UP_GRACE_DAYS));
+
+        kyukos.insert("1".to_owned(), Kyuko { kind: KyukoKind::MakeUp, date: ymd(2017, 6, 26), ..kyuko() });
+        assert_eq!(expiry_date(&k, &kyukos), ymd(2017, 6, 26) + Duration::days(MAKE_UP_GRACE_DAYS));
+
+        let k = Kyuko { kind: KyukoKind::MakeUp, date: ymd(2017, 6, 26), ..kyuko() };
+        assert_eq!(expiry_date(&k, &kyukos), ymd(2017, 6, 26));
+    }
+}
@@ -5,7 +5,6 @@ extern crate chrono;
 #[macro_use]
 extern crate clap;
 extern crate egg_mode;
-extern crate either;
 extern crate env_logger;
 #[macro_use]
 extern crate error_chain;
@@ -15,6 +14,7 @@ extern crate iba_kyuko_bot;
 #[macro_use]
 extern crate log;
 extern crate rand;
+extern crate regex;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -77,16 +77,16 @@ fn run() -> Result<()> {
 
     let working_dir = matches.value_of("WORKING_DIR").unwrap();
 
-    let (mut tweeted, mut users, settings, archive) = config::load(working_dir)?;
+    let (mut tweeted, mut users, mut digests, settings, archive) = config::load(working_dir)?;
     info!("settings: {:?}", settings);
 
     if matches.is_present("clear-users") {
-        admin::clear_users(&mut users)
+        admin::clear_users(&mut users, &mut digests)
     } else if matches.is_present("clear") {
         admin::clear(&mut tweeted, &settings.token.clone().into())
     } else if let Some(ids) = matches.values_of("remove") {
         admin::remove(ids, &mut tweeted, &settings.token.clone().into())
     } else {
-        daemon::run(tweeted, users, settings, archive)
+        daemon::run(tweeted, users, digests, settings, archive)
     }
 }
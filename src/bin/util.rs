@@ -197,10 +197,12 @@ impl<T> DerefMut for SyncFile<T> {
     fn deref_mut(&mut self) -> &mut T { &mut self.data }
 }
 
-/// Shortens `s` and places an ellipsis (`…`) on the end of it if `s.len() > limit`. `limit` must not be zero.
+/// Shortens `s` and places an ellipsis (`…`) on the end of it if `s` has more than `limit` characters. `limit` must
+/// not be zero.
 pub fn shorten(s: &mut String, limit: usize) {
-    if s.len() > limit {
-        s.truncate(limit - 1);
+    if s.chars().count() > limit {
+        let end = s.char_indices().nth(limit - 1).unwrap().0;
+        s.truncate(end);
         s.push('…');
     }
 }
@@ -289,4 +291,16 @@ mod tests {
 
         assert_eq!([1, 2, 3, 4, 4, 5, 5, 6, 7, 8, 10, 11].as_ref(), merged.as_slice());
     }
+    #[test]
+    fn shorten_test() {
+        let mut s = "休講情報".to_owned();
+        shorten(&mut s, 4);
+        assert_eq!(s, "休講情報");
+        shorten(&mut s, 3);
+        assert_eq!(s, "休講…");
+
+        let mut s = "abcdef".to_owned();
+        shorten(&mut s, 1);
+        assert_eq!(s, "…");
+    }
 }
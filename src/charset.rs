@@ -0,0 +1,102 @@
+use encoding::{DecoderTrap, EncodingRef};
+use encoding::all::{UTF_16BE, UTF_16LE, UTF_8};
+use encoding::label::encoding_from_whatwg_label;
+use std::ascii::AsciiExt;
+
+/// Decodes an HTML document into a `String`.
+///
+/// The character encoding is determined by the byte order mark, the `charset` parameter of `content_type` (the value
+/// of the `Content-Type` header) or a `<meta>` element in the first 1024 bytes of the document, in this order. UTF-8 is
+/// assumed if none of them is available. Malformed sequences are replaced with `U+FFFD`.
+pub fn decode(bytes: &[u8], content_type: Option<&str>) -> String {
+    let (enc, bytes) = detect(bytes, content_type);
+    match enc.decode(bytes, DecoderTrap::Replace) {
+        Ok(s) => s,
+        Err(s) => s.into_owned(), // Unreachable with `DecoderTrap::Replace`.
+    }
+}
+
+/// Returns the encoding of `bytes` and the bytes without the byte order mark.
+fn detect<'a>(bytes: &'a [u8], content_type: Option<&str>) -> (EncodingRef, &'a [u8]) {
+    if bytes.starts_with(b"\xEF\xBB\xBF") {
+        return (UTF_8, &bytes[3..]);
+    } else if bytes.starts_with(b"\xFE\xFF") {
+        return (UTF_16BE, &bytes[2..]);
+    } else if bytes.starts_with(b"\xFF\xFE") {
+        return (UTF_16LE, &bytes[2..]);
+    }
+
+    let head = &bytes[..bytes.len().min(1024)];
+    let enc = content_type.and_then(|ct| charset_param(ct.as_bytes()))
+        .and_then(|label| encoding_from_whatwg_label(&label))
+        .or_else(|| charset_param(head).and_then(|label| encoding_from_whatwg_label(&label)))
+        .unwrap_or(UTF_8);
+
+    (enc, bytes)
+}
+
+/// Finds the value of the first `charset` parameter in `s`, e.g. `Shift_JIS` in `text/html; charset=Shift_JIS` or
+/// `<meta charset="Shift_JIS">`.
+fn charset_param(s: &[u8]) -> Option<String> {
+    let lower = s.to_ascii_lowercase();
+    let mut rest = &lower[..];
+
+    while let Some(i) = rest.windows(7).position(|w| w == b"charset") {
+        rest = &rest[i+7..];
+
+        let value = {
+            let mut v = rest.iter().skip_while(|&&c| is_space(c));
+            if v.next() != Some(&b'=') {
+                continue;
+            }
+            v.skip_while(|&&c| is_space(c) || c == b'"' || c == b'\'')
+                .take_while(|&&c| !(is_space(c) || b"\"';>/".contains(&c)))
+                .cloned()
+                .collect::<Vec<u8>>()
+        };
+
+        if !value.is_empty() {
+            return String::from_utf8(value).ok();
+        }
+    }
+
+    None
+}
+
+fn is_space(c: u8) -> bool {
+    b" \t\n\r\x0C".contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_test() {
+        let sjis = b"<p>\x8B\x78\x8D\x75</p>";
+        let euc = b"<p>\xB5\xD9\xB9\xD6</p>";
+
+        assert_eq!(decode(sjis, Some("text/html; charset=Shift_JIS")), "<p>休講</p>");
+        assert_eq!(decode(euc, Some("text/html; charset=\"EUC-JP\"")), "<p>休講</p>");
+        let bom = b"\xEF\xBB\xBF<p>\xE4\xBC\x91\xE8\xAC\x9B</p>";
+        assert_eq!(decode(bom, Some("text/html; charset=Shift_JIS")), "<p>休講</p>");
+
+        let mut meta = b"<html><head><meta charset=\"shift_jis\"></head><body>".to_vec();
+        meta.extend_from_slice(b"\x8B\x78\x8D\x75");
+        assert!(decode(&meta, None).ends_with("休講"));
+
+        let mut meta = b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=EUC-JP\">".to_vec();
+        meta.extend_from_slice(b"\xB5\xD9\xB9\xD6");
+        assert!(decode(&meta, Some("text/html")).ends_with("休講"));
+
+        assert_eq!(decode("<p>休講</p>".as_bytes(), None), "<p>休講</p>");
+    }
+
+    #[test]
+    fn charset_param_test() {
+        assert_eq!(charset_param(b"text/html; charset=Shift_JIS"), Some("shift_jis".to_owned()));
+        assert_eq!(charset_param(b"text/html; charset = 'euc-jp'"), Some("euc-jp".to_owned()));
+        assert_eq!(charset_param(b"<meta charset=utf-8>"), Some("utf-8".to_owned()));
+        assert_eq!(charset_param(b"text/html"), None);
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::Datelike;
+use ::{Field, Kyuko};
+
+/// Differences between previously scraped information and newly scraped information.
+#[derive(Debug)]
+pub struct Diff<'a, K, D: 'a + Datelike> {
+    /// New information that does not correspond to any old one.
+    pub added: Vec<&'a Kyuko<D>>,
+    /// Old information that does not correspond to any new one.
+    pub withdrawn: Vec<(K, &'a Kyuko<D>)>,
+    /// Old information whose corresponding new one has some different fields.
+    pub modified: Vec<Modified<'a, K, D>>,
+}
+
+#[derive(Debug)]
+pub struct Modified<'a, K, D: 'a + Datelike> {
+    pub key: K,
+    pub old: &'a Kyuko<D>,
+    pub new: &'a Kyuko<D>,
+    /// The fields that differ between `old` and `new`.
+    pub changes: Vec<Field>,
+}
+
+/// Compares old information, each of which is associated with a key (e.g. the ID of the Tweet), with new information.
+///
+/// Information is identified by its date, periods, title and lecturer, so that a change of the other fields (e.g. a
+/// fixed typo in the remarks) is reported as a modification rather than a withdrawal and an addition.
+pub fn diff<'a, K, D, I>(old: I, new: &'a [Kyuko<D>]) -> Diff<'a, K, D>
+    where I: IntoIterator<Item=(K, &'a Kyuko<D>)>, D: 'a + Datelike + Eq
+{
+    let old = old.into_iter().collect::<Vec<_>>();
+    let mut old_matched = vec![false; old.len()];
+    let mut new_matched = vec![None; new.len()];
+
+    // Prefer identical pairs so that duplicate entries do not get mixed up.
+    for (i, n) in new.iter().enumerate() {
+        if let Some(j) = (0..old.len()).find(|&j| !old_matched[j] && old[j].1 == n) {
+            old_matched[j] = true;
+            new_matched[i] = Some(j);
+        }
+    }
+
+    for (i, n) in new.iter().enumerate() {
+        if new_matched[i].is_some() {
+            continue;
+        }
+        if let Some(j) = (0..old.len()).find(|&j| !old_matched[j] && old[j].1.is_same_lecture(n)) {
+            old_matched[j] = true;
+            new_matched[i] = Some(j);
+        }
+    }
+
+    let mut old = old.into_iter().map(Some).collect::<Vec<_>>();
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+
+    for (n, j) in new.iter().zip(new_matched) {
+        match j {
+            Some(j) => {
+                let (key, o) = old[j].take().unwrap();
+                let changes = o.changes(n);
+                if !changes.is_empty() {
+                    modified.push(Modified {
+                        key: key,
+                        old: o,
+                        new: n,
+                        changes: changes,
+                    });
+                }
+            },
+            None => added.push(n),
+        }
+    }
+
+    Diff {
+        added: added,
+        withdrawn: old.into_iter().filter_map(|o| o).collect(),
+        modified: modified,
+    }
+}
+
+/// Finds the cancellation that the make-up class `make_up` makes up for among information associated with keys.
+///
+/// If more than one cancellation is found, the latest one is chosen since a make-up class usually follows the
+/// cancellation shortly.
+pub fn find_cancellation<'a, K, D, I>(make_up: &Kyuko<D>, old: I) -> Option<(K, &'a Kyuko<D>)>
+    where I: IntoIterator<Item=(K, &'a Kyuko<D>)>, D: 'a + Datelike + Ord
+{
+    old.into_iter()
+        .filter(|&(_, k)| make_up.is_make_up_of(k))
+        .max_by(|&(_, a), &(_, b)| a.date.cmp(&b.date))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use super::*;
+    use ::{Field, Kyuko, KyukoKind};
+
+    fn kyuko(title: &str, remarks: Option<&str>) -> Kyuko {
+        Kyuko {
+            title: title.to_owned(),
+            remarks: remarks.map(ToOwned::to_owned),
+            ..::tests::kyuko()
+        }
+    }
+
+    #[test]
+    fn diff_test() {
+        let old = [kyuko("線形代数", None), kyuko("微分積分", Some("教室変更")), kyuko("英語", None)];
+        let new = [kyuko("英語", None), kyuko("微分積分", Some("教室変更あり")), kyuko("物理学", None)];
+
+        let d = diff(old.iter().enumerate(), &new);
+
+        assert_eq!(d.added, [&new[2]]);
+        assert_eq!(d.withdrawn.len(), 1);
+        assert_eq!(d.withdrawn[0].0, 0);
+        assert_eq!(d.modified.len(), 1);
+        assert_eq!(d.modified[0].key, 1);
+        assert_eq!(d.modified[0].new, &new[1]);
+        assert_eq!(d.modified[0].changes, [Field::Remarks]);
+    }
+
+    #[test]
+    fn find_cancellation_test() {
+        let mut make_up = kyuko("線形代数", None);
+        make_up.kind = KyukoKind::MakeUp;
+        make_up.date = NaiveDate::from_ymd(2017, 6, 26);
+
+        let mut earlier = kyuko("線形代数", None);
+        earlier.date = NaiveDate::from_ymd(2017, 6, 5);
+        let mut later = kyuko("線形代数", None);
+        later.date = NaiveDate::from_ymd(2017, 7, 3);
+        let old = [earlier, kyuko("線形代数", None), kyuko("英語", None), later];
+
+        let (i, k) = find_cancellation(&make_up, old.iter().enumerate()).unwrap();
+        assert_eq!(i, 1);
+        assert_eq!(k, &old[1]);
+
+        make_up.lecturer = "佐藤".to_owned();
+        assert!(find_cancellation(&make_up, old.iter().enumerate()).is_none());
+    }
+}
@@ -1,45 +1,374 @@
-#![feature(slice_patterns)]
 #![recursion_limit = "1024"]
 
 extern crate chrono;
+extern crate encoding;
 #[macro_use]
 extern crate error_chain;
 extern crate hyper;
 extern crate kuchiki;
-#[macro_use]
-extern crate log;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate unicode_normalization;
 
 pub mod errors {
     error_chain! { }
 }
 
+pub mod charset;
+pub mod diff;
+pub mod normalize;
 pub mod scraper;
+pub mod timetable;
 
 use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::{AsRef, From};
 use std::fmt::{self, Display, Formatter};
 use std::iter::FromIterator;
+use std::str::FromStr;
 
-pub use scraper::scrape;
+pub use diff::{diff, find_cancellation};
+pub use scraper::{parse_remarks, Fallback, Page, RowFailure, RowFallback, Tab, scrape, scrape_bytes, scrape_on};
+pub use timetable::Timetable;
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
 pub struct Kyuko<D: Datelike = NaiveDate> {
-    pub kind: String,
+    pub kind: KyukoKind,
     pub date: D,
+    /// The last date if the information spans multiple days.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<D>,
     pub periods: Periods,
     pub title: String,
     pub lecturer: String,
     pub remarks: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
-pub struct Periods(Vec<u8>);
+/// Structured details extracted from the remarks of lecture information.
+///
+/// The extraction is heuristic:
+///
+/// - `room` is the text following `教室`, `教室変更`, `変更後教室` or `場所` and a `：` up to a space or a
+///   punctuation, e.g. `A101` of `教室：A101` or `変更後教室：A101に変更`. If it contains `→`, the text after the last
+///   one is taken.
+/// - `date` is the first date like `4月13日` or `2017年4月13日` in a line containing `補講日`, `振替日`, `実施日`,
+///   `変更後` or `日時`, or otherwise the first date in the remarks. Dates equal to the date of the information itself
+///   are ignored. The year is inferred from the date of the information if omitted.
+/// - `periods` are the periods written just before `講時` following `date`, e.g. `3` of `4月13日(木) 3講時`.
+/// - `homework` is whether the remarks mention `課題` or `レポート` in a line or sentence that does not contain a
+///   negation like `なし`, `不要` or `ありません`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Details {
+    pub room: Option<String>,
+    pub date: Option<NaiveDate>,
+    pub periods: Option<Periods>,
+    pub homework: bool,
+}
+
+impl Kyuko {
+    /// Extracts structured details from the remarks. See `Details` for the heuristics.
+    pub fn details(&self) -> Details {
+        self.remarks.as_ref().map_or_else(Details::default, |r| scraper::parse_remarks(r, self.date))
+    }
+}
+
+impl<D: Datelike + Eq> Kyuko<D> {
+    /// Returns the normalized names of the lecturers, which are written in `lecturer` as e.g. `山田・佐藤 他`.
+    pub fn lecturers(&self) -> Vec<String> {
+        normalize::split_lecturers(&self.lecturer)
+    }
+
+    /// Returns whether the two pieces of information are about the same lecture, i.e. whether they have the same
+    /// date, periods, title and lecturer.
+    ///
+    /// Texts are compared ignoring differences in whitespace and line breaks (see `changes`).
+    pub fn is_same_lecture(&self, other: &Kyuko<D>) -> bool {
+        self.date == other.date && self.periods == other.periods && same_text(&self.title, &other.title)
+            && same_text(&self.lecturer, &other.lecturer)
+    }
+
+    /// Returns the fields that differ from `other`.
+    ///
+    /// Texts that differ only in whitespace and line breaks are regarded as the same, since the text of information
+    /// stored by older versions was extracted from the cells differently.
+    pub fn changes(&self, other: &Kyuko<D>) -> Vec<Field> {
+        let mut ret = Vec::new();
+
+        let same_remarks = match (&self.remarks, &other.remarks) {
+            (&Some(ref a), &Some(ref b)) => same_text(a, b),
+            (&None, &None) => true,
+            _ => false,
+        };
+
+        if self.kind != other.kind { ret.push(Field::Kind); }
+        if self.date != other.date || self.until != other.until { ret.push(Field::Date); }
+        if self.periods != other.periods { ret.push(Field::Periods); }
+        if !same_text(&self.title, &other.title) { ret.push(Field::Title); }
+        if !same_text(&self.lecturer, &other.lecturer) { ret.push(Field::Lecturer); }
+        if !same_remarks { ret.push(Field::Remarks); }
+
+        ret
+    }
+
+    /// Returns whether the only change from `self` to `new` is that the remarks have been completed, i.e. the remarks
+    /// of `self` are missing or a prefix of those of `new`.
+    ///
+    /// Older versions took only the first text node of the remarks, so every piece of information stored by them with
+    /// remarks changes in this way when it is scraped again.
+    pub fn has_truncated_remarks(&self, new: &Kyuko<D>) -> bool {
+        if self.changes(new) != [Field::Remarks] {
+            return false;
+        }
+
+        match (&self.remarks, &new.remarks) {
+            (&None, &Some(_)) => true,
+            (&Some(ref before), &Some(ref after)) => {
+                let join = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+                join(after).starts_with(&join(before))
+            },
+            _ => false,
+        }
+    }
+}
+
+impl<D: Datelike + Ord> Kyuko<D> {
+    /// Returns whether `self` can be the make-up class of `cancellation`, i.e. whether `self` is a make-up class of
+    /// the same title and lecturer as the cancelled class `cancellation` and takes place after it.
+    pub fn is_make_up_of(&self, cancellation: &Kyuko<D>) -> bool {
+        self.kind == KyukoKind::MakeUp && cancellation.kind == KyukoKind::Cancellation
+            && self.title == cancellation.title && self.lecturer == cancellation.lecturer
+            && self.date > cancellation.date
+    }
+}
+
+/// Returns whether `a` and `b` are the same text except for whitespace and line breaks.
+fn same_text(a: &str, b: &str) -> bool {
+    a.split_whitespace().eq(b.split_whitespace())
+}
+
+/// The kind of a piece of lecture information.
+///
+/// It is serialized as its Japanese label (e.g. `休講`) so that it stays compatible with the plain strings
+/// that were stored before this type was introduced.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum KyukoKind {
+    /// 休講
+    Cancellation,
+    /// 補講
+    MakeUp,
+    /// 教室変更
+    RoomChange,
+    /// 時間変更
+    TimeChange,
+    /// その他
+    Misc,
+    /// A label unknown to this library.
+    Other(String),
+}
+
+/// A field of a piece of lecture information, or `Row` for the structure of the table row itself.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Field {
+    Row,
+    Kind,
+    Date,
+    Periods,
+    Title,
+    Lecturer,
+    Remarks,
+}
+
+/// The periods of a lecture.
+///
+/// `Numbered` periods should be constructed with `From<Vec<u8>>` or `FromIterator<u8>`, which sort and deduplicate
+/// the numbers.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Periods {
+    Numbered(Vec<u8>),
+    /// 終日
+    AllDay,
+    /// 午前
+    Morning,
+    /// 午後
+    Afternoon,
+    /// Periods that are not specified, e.g. of 集中講義.
+    Unspecified,
+}
+
+impl KyukoKind {
+    /// Determines the kind from a label like `休講` or `【休講】`.
+    pub fn from_label(label: &str) -> Self {
+        use KyukoKind::*;
+
+        let label = label.trim();
+        let label = label.trim_left_matches('【').trim_right_matches('】').trim();
+
+        match label {
+            "休講" => Cancellation,
+            "補講" => MakeUp,
+            "教室変更" => RoomChange,
+            "時間変更" => TimeChange,
+            "その他" => Misc,
+            _ => Other(label.to_owned()),
+        }
+    }
+
+    /// Determines the kind from the path of its icon (e.g. `img/hokou.png`), returning `None` if the file name
+    /// is unknown.
+    pub fn from_icon(src: &str) -> Option<Self> {
+        use KyukoKind::*;
+
+        let name = src.rsplit('/').next().unwrap_or(src);
+        let stem = name.split('.').next().unwrap_or(name);
+
+        match stem {
+            "kyuko" | "kyukou" => Some(Cancellation),
+            "hoko" | "hokou" => Some(MakeUp),
+            "kyoshitsu" | "kyositu" | "heya" => Some(RoomChange),
+            "jikan" => Some(TimeChange),
+            "sonota" => Some(Misc),
+            _ => None,
+        }
+    }
+
+    /// Returns the Japanese label of the kind, as shown on the university's website.
+    pub fn ja(&self) -> &str {
+        use KyukoKind::*;
+
+        match *self {
+            Cancellation => "休講",
+            MakeUp => "補講",
+            RoomChange => "教室変更",
+            TimeChange => "時間変更",
+            Misc => "その他",
+            Other(ref s) => s,
+        }
+    }
+
+    /// Returns the English label of the kind. Unknown kinds are labeled as is.
+    pub fn en(&self) -> &str {
+        use KyukoKind::*;
+
+        match *self {
+            Cancellation => "cancellation",
+            MakeUp => "make-up class",
+            RoomChange => "room change",
+            TimeChange => "time change",
+            Misc => "other",
+            Other(ref s) => s,
+        }
+    }
+}
+
+impl Display for KyukoKind {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(self.ja())
+    }
+}
+
+impl Field {
+    /// Returns the Japanese name of the field.
+    pub fn ja(&self) -> &'static str {
+        use Field::*;
+
+        match *self {
+            Row => "行",
+            Kind => "種別",
+            Date => "日付",
+            Periods => "時限",
+            Title => "科目",
+            Lecturer => "教員",
+            Remarks => "備考",
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        use Field::*;
+
+        f.write_str(match *self {
+            Row => "row",
+            Kind => "kind",
+            Date => "date",
+            Periods => "periods",
+            Title => "title",
+            Lecturer => "lecturer",
+            Remarks => "remarks",
+        })
+    }
+}
+
+impl Serialize for KyukoKind {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.ja())
+    }
+}
+
+impl Deserialize for KyukoKind {
+    fn deserialize<D: Deserializer>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d).map(|s| KyukoKind::from_label(&s))
+    }
+}
+
+/// The last period in the morning.
+const LAST_MORNING_PERIOD: u8 = 2;
+
+impl Periods {
+    pub fn is_numbered(&self) -> bool {
+        if let Periods::Numbered(_) = *self { true } else { false }
+    }
+
+    /// Returns whether the periods include the `n`th period.
+    pub fn covers(&self, n: u8) -> bool {
+        use Periods::*;
+
+        match *self {
+            Numbered(ref v) => v.contains(&n),
+            AllDay => true,
+            Morning => n <= LAST_MORNING_PERIOD,
+            Afternoon => n > LAST_MORNING_PERIOD,
+            Unspecified => false,
+        }
+    }
+
+    /// Returns whether the periods have any period in common with `other`.
+    ///
+    /// `Unspecified` periods only overlap with `Unspecified` ones.
+    pub fn overlaps(&self, other: &Periods) -> bool {
+        use Periods::*;
+
+        match (self, other) {
+            (&Numbered(ref v), p) | (p, &Numbered(ref v)) => v.iter().any(|&n| p.covers(n)),
+            (&Unspecified, &Unspecified) => true,
+            (&Unspecified, _) | (_, &Unspecified) => false,
+            (&AllDay, _) | (_, &AllDay) => true,
+            (p, q) => p == q,
+        }
+    }
+}
+
+impl FromStr for Periods {
+    type Err = errors::Error;
+
+    /// Parses periods written in the same way as the website, e.g. `1-3,5講時` or `午前`.
+    fn from_str(s: &str) -> errors::Result<Self> {
+        scraper::parse_periods(s)
+    }
+}
 
 impl Display for Periods {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
-        let mut iter = self.0.iter();
+        let v = match *self {
+            Periods::Numbered(ref v) => v,
+            Periods::AllDay => return f.write_str("終日"),
+            Periods::Morning => return f.write_str("午前"),
+            Periods::Afternoon => return f.write_str("午後"),
+            Periods::Unspecified => return f.write_str("時限指定なし"),
+        };
+
+        let mut iter = v.iter();
 
         if let Some(&n) = iter.next() {
             let mut start = n;
@@ -73,7 +402,7 @@ impl From<Vec<u8>> for Periods {
         v.sort();
         v.dedup();
         v.shrink_to_fit();
-        Periods(v)
+        Periods::Numbered(v)
     }
 }
 
@@ -84,8 +413,65 @@ impl FromIterator<u8> for Periods {
 }
 
 impl AsRef<[u8]> for Periods {
+    /// Returns the numbered periods, or an empty slice if the periods are not numbered.
     fn as_ref(&self) -> &[u8] {
-        self.0.as_ref()
+        match *self {
+            Periods::Numbered(ref v) => v.as_ref(),
+            _ => &[],
+        }
+    }
+}
+
+/// `Numbered` periods are serialized as a sequence of numbers and the others as a string like `all_day`.
+impl Serialize for Periods {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Periods::Numbered(ref v) => v.serialize(s),
+            Periods::AllDay => s.serialize_str("all_day"),
+            Periods::Morning => s.serialize_str("morning"),
+            Periods::Afternoon => s.serialize_str("afternoon"),
+            Periods::Unspecified => s.serialize_str("unspecified"),
+        }
+    }
+}
+
+impl Deserialize for Periods {
+    fn deserialize<D: Deserializer>(d: D) -> Result<Self, D::Error> {
+        use serde::de::{Error, SeqVisitor, Visitor};
+
+        struct PeriodsVisitor;
+
+        impl Visitor for PeriodsVisitor {
+            type Value = Periods;
+
+            fn visit_seq<V: SeqVisitor>(self, mut v: V) -> Result<Periods, V::Error> {
+                let mut ret = Vec::with_capacity(v.size_hint().0);
+                while let Some(n) = v.visit()? {
+                    ret.push(n);
+                }
+                Ok(Periods::from(ret))
+            }
+
+            fn visit_str<E: Error>(self, s: &str) -> Result<Periods, E> {
+                match s {
+                    "all_day" => Ok(Periods::AllDay),
+                    "morning" => Ok(Periods::Morning),
+                    "afternoon" => Ok(Periods::Afternoon),
+                    "unspecified" => Ok(Periods::Unspecified),
+                    _ => Err(E::custom(format!("unknown periods: {}", s))),
+                }
+            }
+
+            fn visit_string<E: Error>(self, s: String) -> Result<Periods, E> {
+                self.visit_str(&s)
+            }
+
+            fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+                write!(f, "a sequence of numbers or a string")
+            }
+        }
+
+        d.deserialize(PeriodsVisitor)
     }
 }
 
@@ -94,6 +480,29 @@ mod tests {
     use super::*;
     use std::fmt::Write;
 
+    /// Returns a cancellation of `線形代数` by `山田` on 2017-06-12, 3講時, to be modified by each test.
+    pub fn kyuko() -> Kyuko {
+        Kyuko {
+            kind: KyukoKind::Cancellation,
+            date: NaiveDate::from_ymd(2017, 6, 12),
+            until: None,
+            periods: Periods::from(vec![3]),
+            title: "線形代数".to_owned(),
+            lecturer: "山田".to_owned(),
+            remarks: None,
+        }
+    }
+
+    #[test]
+    fn kyuko_kind() {
+        assert_eq!(KyukoKind::from_label("【休講】"), KyukoKind::Cancellation);
+        assert_eq!(KyukoKind::from_label("補講"), KyukoKind::MakeUp);
+        assert_eq!(KyukoKind::from_label("【集中講義】"), KyukoKind::Other("集中講義".to_owned()));
+        assert_eq!(KyukoKind::from_icon("img/hokou.png"), Some(KyukoKind::MakeUp));
+        assert_eq!(KyukoKind::from_icon("img/unknown.png"), None);
+        assert_eq!(KyukoKind::Other("集中講義".to_owned()).en(), "集中講義");
+    }
+
     #[test]
     fn periods_display() {
         macro_rules! test_eq {
@@ -114,5 +523,70 @@ mod tests {
         test_eq!([1,3,4,5], "1,3-5");
         test_eq!([1,3,4,6], "1,3-4,6");
         test_eq!([6,4,1,2], "1-2,4,6");
+        test_eq!([9,10,11], "9-11");
+
+        assert_eq!(Periods::AllDay.to_string(), "終日");
+        assert_eq!(Periods::Morning.to_string(), "午前");
+    }
+
+    #[test]
+    fn kyuko_lecturers() {
+        let k = Kyuko { lecturer: "山田　太郎・佐藤 他".to_owned(), ..kyuko() };
+        assert_eq!(k.lecturers(), ["山田 太郎", "佐藤"]);
+        assert_eq!(k.lecturer, "山田　太郎・佐藤 他");
+    }
+
+    #[test]
+    fn kyuko_changes() {
+        let old = Kyuko {
+            title: "線形代数 I".to_owned(),
+            lecturer: "山田  太郎".to_owned(),
+            remarks: Some("補講日：6月19日  教室：A101".to_owned()),
+            ..kyuko()
+        };
+        let mut new = Kyuko {
+            title: "線形代数\nI".to_owned(),
+            lecturer: "山田 太郎".to_owned(),
+            remarks: Some("補講日：6月19日\n教室：A101".to_owned()),
+            ..old.clone()
+        };
+        assert!(old.is_same_lecture(&new));
+        assert!(old.changes(&new).is_empty());
+
+        new.remarks = Some("補講日：6月19日\n教室：A102".to_owned());
+        assert_eq!(old.changes(&new), [Field::Remarks]);
+        new.remarks = None;
+        assert_eq!(old.changes(&new), [Field::Remarks]);
+    }
+
+    #[test]
+    fn kyuko_has_truncated_remarks() {
+        let old = Kyuko { remarks: Some("補講日：".to_owned()), ..kyuko() };
+        let mut new = Kyuko { remarks: Some("補講日：6月19日\n教室： A101".to_owned()), ..old.clone() };
+        assert!(old.has_truncated_remarks(&new));
+        assert!(Kyuko { remarks: None, ..old.clone() }.has_truncated_remarks(&new));
+
+        new.remarks = Some("振替日：6月19日".to_owned());
+        assert!(!old.has_truncated_remarks(&new));
+        new.remarks = None;
+        assert!(!old.has_truncated_remarks(&new));
+        new.remarks = Some("補講日：6月19日".to_owned());
+        new.periods = Periods::from(vec![4]);
+        assert!(!old.has_truncated_remarks(&new));
+    }
+
+    #[test]
+    fn periods_overlaps() {
+        let p = |v: &[u8]| v.iter().cloned().collect::<Periods>();
+
+        assert!(p(&[1, 2]).overlaps(&p(&[2, 3])));
+        assert!(!p(&[1, 2]).overlaps(&p(&[3, 4])));
+        assert!(p(&[1]).overlaps(&Periods::Morning));
+        assert!(!Periods::Afternoon.overlaps(&p(&[2])));
+        assert!(Periods::AllDay.overlaps(&p(&[5])));
+        assert!(Periods::AllDay.overlaps(&Periods::Afternoon));
+        assert!(!Periods::Morning.overlaps(&Periods::Afternoon));
+        assert!(!Periods::AllDay.overlaps(&Periods::Unspecified));
+        assert!(Periods::Unspecified.overlaps(&Periods::Unspecified));
     }
 }
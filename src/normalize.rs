@@ -0,0 +1,145 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Options of `fold` in addition to the normalization of `normalize`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub struct FoldOptions {
+    /// Whether to ignore the case of letters.
+    #[serde(default = "default_true")]
+    pub case: bool,
+    /// Whether to regard katakana as the corresponding hiragana.
+    #[serde(default = "default_true")]
+    pub kana: bool,
+}
+
+impl Default for FoldOptions {
+    fn default() -> Self {
+        FoldOptions {
+            case: true,
+            kana: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Normalizes a string for display.
+///
+/// The string is normalized into NFKC, by which e.g. full-width ASCII characters (`Ａ`, `１`), half-width katakana
+/// (`ｶ`) and the ideographic space are converted into their usual forms. Runs of whitespace are then collapsed into a
+/// single space and trimmed.
+pub fn normalize(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    let mut space = false;
+
+    for c in s.nfkc() {
+        if c.is_whitespace() {
+            space = true;
+        } else {
+            if space && !ret.is_empty() {
+                ret.push(' ');
+            }
+            space = false;
+            ret.push(c);
+        }
+    }
+
+    ret
+}
+
+/// Normalizes a string for matching.
+///
+/// In addition to `normalize`, whitespace is removed entirely so that e.g. `山田　太郎` and `山田太郎` are
+/// equivalent, and letters and katakana are folded according to `options`.
+pub fn fold(s: &str, options: FoldOptions) -> String {
+    let mut ret = String::with_capacity(s.len());
+
+    for c in s.nfkc().filter(|c| !c.is_whitespace()) {
+        let c = if options.kana { hiragana(c) } else { c };
+        if options.case {
+            ret.extend(c.to_lowercase());
+        } else {
+            ret.push(c);
+        }
+    }
+
+    ret
+}
+
+/// Converts the katakana in a string into the corresponding hiragana in the same way as `fold` with
+/// `FoldOptions::kana`, leaving the other characters as they are.
+pub fn fold_kana(s: &str) -> String {
+    s.chars().map(hiragana).collect()
+}
+
+/// Converts a katakana into the corresponding hiragana.
+fn hiragana(c: char) -> char {
+    match c {
+        'ァ'...'ヶ' | 'ヽ' | 'ヾ' => ::std::char::from_u32(c as u32 - 0x60).unwrap(),
+        _ => c,
+    }
+}
+
+/// Splits the lecturer field of lecture information (e.g. `山田・佐藤 他`) into normalized names of the lecturers.
+///
+/// Names are separated by `・`, `,`, `、`, `/` or their full-width variants, and a trailing `他` or `ほか`
+/// (meaning "and others") is removed.
+pub fn split_lecturers(s: &str) -> Vec<String> {
+    normalize(s)
+        .split(|c: char| "・･,、/".contains(c))
+        .map(|name| {
+            let name = name.trim();
+            let name = if name.ends_with("ほか") {
+                &name[..name.len() - "ほか".len()]
+            } else if name.ends_with('他') {
+                &name[..name.len() - '他'.len_utf8()]
+            } else {
+                name
+            };
+            name.trim().to_owned()
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_test() {
+        assert_eq!(normalize("山田　太郎"), "山田 太郎");
+        assert_eq!(normalize(" Ｊｏｈｎ  Ｓｍｉｔｈ\n"), "John Smith");
+        assert_eq!(normalize("線形代数Ⅰ（１）"), "線形代数I(1)");
+        assert_eq!(normalize("ﾌﾟﾛｸﾞﾗﾐﾝｸﾞ"), "プログラミング");
+    }
+
+    #[test]
+    fn fold_test() {
+        let opts = FoldOptions::default();
+        assert_eq!(fold("ＡＩ入門", opts), fold("AI入門", opts));
+        assert_eq!(fold("ﾌﾟﾛｸﾞﾗﾐﾝｸﾞ", opts), fold("ぷろぐらみんぐ", opts));
+        assert_eq!(fold("山田　太郎", opts), "山田太郎");
+        assert_eq!(fold("English Ⅱ", opts), "englishii");
+
+        let opts = FoldOptions { case: false, kana: false };
+        assert_eq!(fold("Ａ ｶ", opts), "Aカ");
+    }
+
+    #[test]
+    fn fold_kana_test() {
+        assert_eq!(fold_kana("エイゴ Ⅰ"), "えいご Ⅰ");
+        assert_eq!(fold_kana("[ア-ン]+"), "[あ-ん]+");
+    }
+
+    #[test]
+    fn split_lecturers_test() {
+        assert_eq!(split_lecturers("山田"), ["山田"]);
+        assert_eq!(split_lecturers("山田・佐藤"), ["山田", "佐藤"]);
+        assert_eq!(split_lecturers("山田, 佐藤 他"), ["山田", "佐藤"]);
+        assert_eq!(split_lecturers("山田　太郎、佐藤ほか"), ["山田 太郎", "佐藤"]);
+        assert_eq!(split_lecturers("Ｊ．Ｓｍｉｔｈ／山田"), ["J.Smith", "山田"]);
+        assert!(split_lecturers("").is_empty());
+    }
+}
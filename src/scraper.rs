@@ -1,14 +1,122 @@
 extern crate tendril;
 
+use chrono::NaiveDate;
 use errors::*;
 use kuchiki::parse_html;
 use kuchiki::traits::*;
 use self::tendril::Tendril;
 use self::tendril::fmt::UTF8;
 use self::util::*;
-use ::Kyuko;
+use ::{Details, Field, Kyuko, Periods, charset};
+
+/// Lecture information scraped from a department's page.
+#[derive(Debug)]
+pub struct Page {
+    /// The department tabs listed on the page, in the order of appearance.
+    pub tabs: Vec<Tab>,
+    pub kyukos: Vec<Kyuko>,
+    /// The rows of the table that could not be parsed into `Kyuko`s.
+    pub failures: Vec<RowFailure>,
+    /// The lenient interpretations applied to rows that do not follow the usual format.
+    pub fallbacks: Vec<RowFallback>,
+}
+
+/// A row of the table of lecture information that failed to be parsed.
+#[derive(Clone, Debug)]
+pub struct RowFailure {
+    /// The index of the row (`<tbody>`) in the table, starting from zero.
+    pub index: usize,
+    /// The field that failed to be parsed.
+    pub field: Field,
+    pub message: String,
+    /// The raw HTML of the row.
+    pub html: String,
+}
+
+/// A lenient interpretation applied to a row of the table.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RowFallback {
+    /// The index of the row (`<tbody>`) in the table, starting from zero.
+    pub index: usize,
+    pub field: Field,
+    pub fallback: Fallback,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Fallback {
+    /// The year was missing and has been inferred from the crawl date.
+    InferredYear,
+    /// The date contained full-width digits.
+    FullWidthDigits,
+    /// The month or the day was not zero-padded.
+    UnpaddedNumber,
+    /// The date contained whitespace.
+    Whitespace,
+    /// The date was a range of dates like `4月6日～8日`.
+    DateRange,
+}
+
+/// A tab of a department, e.g. `<a href="index-student2.php?g=4" class="tab">人文学部<br>（研究科）<br>1件</a>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Tab {
+    /// The name of the department, e.g. `人文学部`.
+    pub name: String,
+    /// The suffix shown below the name without the parentheses, e.g. `研究科`.
+    pub suffix: Option<String>,
+    /// The number of items the website claims to have for the department.
+    pub count: Option<u32>,
+    /// The `href` attribute of the tab as is, e.g. `index-student2.php?g=4`.
+    pub href: String,
+    pub selected: bool,
+}
+
+impl Page {
+    /// Returns the tab of the department the page belongs to.
+    pub fn department(&self) -> &Tab {
+        // `scrape` ensures that there is a selected tab.
+        self.tabs.iter().find(|t| t.selected).unwrap()
+    }
+
+    /// Returns the number of items the department's tab claims to have if it differs from the number of rows found
+    /// on the page (including `failures`), which suggests that the layout of the website has changed.
+    pub fn count_mismatch(&self) -> Option<u32> {
+        self.department().count.and_then(|n| {
+            if n as usize == self.kyukos.len() + self.failures.len() { None } else { Some(n) }
+        })
+    }
+}
+
+/// Scrapes a page, assuming that it has been crawled today in the local time zone.
+pub fn scrape<T: Into<Tendril<UTF8>>>(html: T) -> Result<Page> {
+    use chrono::Local;
+
+    scrape_on(html, Local::today().naive_local())
+}
+
+/// Scrapes a page from its raw bytes, decoding them with the encoding determined by `content_type` (the value of the
+/// `Content-Type` header) or the document itself. See `charset::decode` for details.
+pub fn scrape_bytes(html: &[u8], content_type: Option<&str>) -> Result<Page> {
+    scrape(charset::decode(html, content_type))
+}
+
+/// Extracts structured details from the remarks of lecture information dated `date`. See `Details` for the heuristics.
+pub fn parse_remarks(remarks: &str, date: NaiveDate) -> Details {
+    util::parse_remarks(remarks, date)
+}
+
+/// Parses a string representing periods, e.g. `1-3,5講時` or `午前`.
+pub fn parse_periods(src: &str) -> Result<Periods> {
+    util::parse_periods(src)
+}
 
-pub fn scrape<T: Into<Tendril<UTF8>>>(html: T) -> Result<(String, Vec<Kyuko>)> {
+/// Completes a date written without the year (e.g. `6月12日`) in the same way as the dates of the website, assuming
+/// that it is near `today`.
+pub fn infer_year(month: u32, day: u32, today: NaiveDate) -> Option<NaiveDate> {
+    util::infer_year(month, day, today)
+}
+
+/// Scrapes a page crawled on `today`, which is used to infer the years of dates that lack ones.
+pub fn scrape_on<T: Into<Tendril<UTF8>>>(html: T, today: NaiveDate) -> Result<Page> {
     // <!-- Example DOM tree (extract) -->
     //
     // <div id="tabbox">
@@ -40,78 +148,140 @@ pub fn scrape<T: Into<Tendril<UTF8>>>(html: T) -> Result<(String, Vec<Kyuko>)> {
 
     let document = parse_html().one(html);
 
-    let dept = document
-        .select("#tabbox > .gakubulist > a.selected")
+    let tabs = document
+        .select("#tabbox > .gakubulist > a.tab")
         .expect("failed to parse the selector")
-        .next().ok_or_else::<Error,_>(|| "unable to determine the department".into())?
-        .as_node().children().next().ok_or_else::<Error,_>(|| "expected text node".into())?;
-    let dept = dept.as_text().ok_or_else::<Error,_>(|| "expected text node".into())?.borrow().clone();
+        .map(|a| parse_tab(a.as_node()))
+        .collect::<Result<Vec<_>>>()?;
 
-    let mut kyukos = Vec::new();
+    if !tabs.iter().any(|t| t.selected) {
+        return Err("unable to determine the department".into());
+    }
 
-    for tbody in document.select("#eventlist > table.citem > tbody").expect("failed to parse the selector") {
-        match parse_kyuko_tbody(tbody.as_node()) {
-            Ok(k) => { kyukos.push(k); },
-            Err(e) => {
-                error!("error: {}", e.description());
+    let mut kyukos = Vec::new();
+    let mut failures = Vec::new();
+    let mut fallbacks = Vec::new();
+
+    let tbodies = document.select("#eventlist > table.citem > tbody").expect("failed to parse the selector");
+    for (i, tbody) in tbodies.enumerate() {
+        let mut row_fallbacks = Vec::new();
+        match parse_kyuko_tbody(tbody.as_node(), today, &mut row_fallbacks) {
+            Ok(k) => {
+                kyukos.push(k);
+                fallbacks.extend(row_fallbacks.into_iter().map(|f| RowFallback {
+                    index: i,
+                    field: Field::Date,
+                    fallback: f,
+                }));
+            },
+            Err((field, e)) => {
+                let mut message = e.to_string();
                 for e in e.iter().skip(1) {
-                    error!("caused by: {}", e);
+                    message.push_str("; caused by: ");
+                    message.push_str(&e.to_string());
                 }
+                failures.push(RowFailure {
+                    index: i,
+                    field: field,
+                    message: message,
+                    html: tbody.as_node().to_string(),
+                });
             },
         }
     }
 
-    Ok((dept, kyukos))
+    Ok(Page {
+        tabs: tabs,
+        kyukos: kyukos,
+        failures: failures,
+        fallbacks: fallbacks,
+    })
 }
 
 mod util {
-    use chrono::NaiveDate;
+    use chrono::{Datelike, NaiveDate};
     use errors::*;
     use kuchiki::NodeRef;
-    use ::{Kyuko, Periods};
-
-    pub fn parse_kyuko_tbody(tbody: &NodeRef) -> Result<Kyuko> {
-        macro_rules! msg {
-            ($s:expr) => (|| <String as Into<Error>>::into(format!("{}; DOM tree: {}", $s, tbody.to_string())));
-            () => (msg!("failed to parse class information"));
+    use std::u8;
+    use super::{Fallback, Tab};
+    use ::{Details, Field, Kyuko, KyukoKind, Periods};
+
+    pub fn parse_tab(a: &NodeRef) -> Result<Tab> {
+        let msg = || -> Error { format!("failed to parse a department tab; DOM tree: {}", a.to_string()).into() };
+
+        let elm = a.as_element().ok_or_else(&msg)?;
+        let attrs = elm.attributes.borrow();
+        let href = attrs.get("href").ok_or_else(&msg)?.to_owned();
+        let selected = attrs.get("class").map_or(false, |c| c.split_whitespace().any(|c| c == "selected"));
+
+        // The text nodes are separated by `<br>`s: the name, the suffix (or `&nbsp;`) and the number of items.
+        let mut name = None;
+        let mut suffix = None;
+        let mut count = None;
+
+        for text in a.children().filter_map(|node| node.as_text().map(|t| t.borrow().trim().to_owned())) {
+            if text.is_empty() {
+                continue;
+            } else if name.is_none() {
+                name = Some(text);
+            } else if text.ends_with('件') {
+                count = text.trim_right_matches('件').trim().parse().ok();
+            } else {
+                let s = text.trim_left_matches(|c| c == '（' || c == '(').trim_right_matches(|c| c == '）' || c == ')');
+                suffix = Some(s.trim().to_owned());
+            }
         }
 
-        let mut trs = tbody.children().filter(|node| node.as_element().is_some());
-
-        let mut tds = trs.next().ok_or_else(msg!("expected <tr>"))?.children()
-            .filter(|node| node.as_element().is_some()); // TODO: replace /w mem::discriminant once it's stabilized.
+        Ok(Tab {
+            name: name.ok_or_else(&msg)?,
+            suffix: suffix,
+            count: count,
+            href: href,
+            selected: selected,
+        })
+    }
 
-        let kind = tds.next().ok_or_else(msg!("expected <td> for information kind"))?
-            .first_child().ok_or_else(msg!("expected element for information kind"))?;
-        let mut kind = kind.as_element().ok_or_else(msg!("expected element for information kind"))?
-            .attributes.borrow()
-            .get("alt").ok_or_else(msg!("expected alt attribute for information kind"))?.to_string();
-        if kind.ends_with('】') {
-            kind.pop();
-        }
-        if kind.starts_with('【') {
-            kind.remove(0);
+    pub fn parse_kyuko_tbody(tbody: &NodeRef, today: NaiveDate, fallbacks: &mut Vec<Fallback>)
+        -> ::std::result::Result<Kyuko, (Field, Error)>
+    {
+        macro_rules! fail {
+            ($field:ident, $s:expr) => (|| (Field::$field, Error::from($s)));
         }
 
-        let date = tds.next().ok_or_else(msg!("expected <td> for date"))?
-            .first_child().ok_or_else(msg!("expected text node for date"))?;
-        let date = date.as_text().ok_or_else(msg!("expected text node for date"))?.borrow();
-        let (date, periods) = parse_date(&date).chain_err(msg!())?;
+        let mut trs = tbody.children().filter(|node| node.as_element().is_some());
 
-        let title = tds.next().ok_or_else(msg!("expected <td>"))?
-            .first_child().ok_or_else(msg!("expected text node for class title"))?;
-        let title = title.as_text().ok_or_else(msg!("expected text node for class title"))?.borrow();
-        let (title, lecturer) = parse_kamoku(&title)?;
+        let mut tds = trs.next().ok_or_else(fail!(Row, "expected <tr>"))?.children()
+            .filter(|node| node.as_element().is_some()); // TODO: replace /w mem::discriminant once it's stabilized.
 
-        let remarks = trs.next().ok_or_else(msg!("expected <tr> for remarks"))?
-            .first_child().ok_or_else(msg!("expected <td> for remarks"))?
-            .first_child().and_then(|text| {
-                text.as_text().map(|t| t.borrow().clone())
-            });
+        let kind = tds.next().ok_or_else(fail!(Row, "expected <td> for information kind"))?
+            .first_child().ok_or_else(fail!(Kind, "expected element for information kind"))?;
+        let kind = kind.as_element().ok_or_else(fail!(Kind, "expected element for information kind"))?
+            .attributes.borrow();
+        // Prefer the `alt` text and fall back to the file name of the icon if the text is missing or unknown.
+        let kind = match (kind.get("alt").map(KyukoKind::from_label), kind.get("src").and_then(KyukoKind::from_icon)) {
+            (Some(KyukoKind::Other(_)), Some(k)) | (None, Some(k)) => k,
+            (Some(k), _) => k,
+            (None, None) => return Err(fail!(Kind, "expected alt or src attribute for information kind")()),
+        };
+
+        let date = tds.next().ok_or_else(fail!(Row, "expected <td> for date"))?;
+        let date = cell_text(&date, " ");
+        let ((date, until), periods) = parse_date(&date, today, fallbacks)?;
+
+        let title = tds.next().ok_or_else(fail!(Row, "expected <td> for class title"))?;
+        let title = cell_text(&title, " ");
+        let (title, lecturer) = parse_kamoku(&title).map_err(|e| (Field::Lecturer, e))?;
+
+        let remarks = trs.next().ok_or_else(fail!(Row, "expected <tr> for remarks"))?
+            .children().find(|node| node.as_element().is_some())
+            .ok_or_else(fail!(Remarks, "expected <td> for remarks"))?;
+        let remarks = cell_text(&remarks, "\n");
+        let remarks = if remarks.is_empty() { None } else { Some(remarks) };
 
         Ok(Kyuko {
             kind: kind,
             date: date,
+            until: until,
             periods: periods,
             title: title,
             lecturer: lecturer,
@@ -119,16 +289,322 @@ mod util {
         })
     }
 
-    pub fn parse_date(src: &str) -> Result<(NaiveDate, Periods)> {
-        let date = src.split('(')
-            .next().ok_or_else::<Error,_>(|| format!("unable to find date string in: {}", src).into())?;
-        let date = parse_date_str(date)?;
+    /// Extracts the text of a table cell, regarding `<br>` and block elements as line breaks.
+    ///
+    /// Whitespace in each line is collapsed, and the non-empty lines are joined with `line_sep`.
+    pub fn cell_text(td: &NodeRef, line_sep: &str) -> String {
+        fn push_text(node: &NodeRef, buf: &mut String) {
+            for child in node.children() {
+                if let Some(text) = child.as_text() {
+                    buf.push_str(&text.borrow());
+                } else if let Some(elm) = child.as_element() {
+                    match &*elm.name.local {
+                        "br" => buf.push('\n'),
+                        "div" | "p" | "li" | "tr" => {
+                            buf.push('\n');
+                            push_text(&child, buf);
+                            buf.push('\n');
+                        },
+                        _ => push_text(&child, buf),
+                    }
+                }
+            }
+        }
+
+        let mut buf = String::new();
+        push_text(td, &mut buf);
+
+        buf.lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(line_sep)
+    }
+
+    /// Parses the content of a date cell, e.g. `2016年04月06日(水)　1.2講時`.
+    ///
+    /// The date may lack the year, contain full-width digits, unpadded numbers or whitespace, or be a range like
+    /// `4月6日(水)～8日(金)`. Such lenient interpretations are pushed to `fallbacks`.
+    pub fn parse_date(src: &str, today: NaiveDate, fallbacks: &mut Vec<Fallback>)
+        -> ::std::result::Result<((NaiveDate, Option<NaiveDate>), Periods), (Field, Error)>
+    {
+        let err = || (Field::Date, Error::from(format!("unable to parse the date: {}", src)));
+
+        let chars = src.trim().chars().collect::<Vec<_>>();
+        let mut cur = DateCursor { chars: &chars, pos: 0, fallbacks: Vec::new() };
+
+        let start = cur.ymd().ok_or_else(&err)?;
+        let (start_month, start_day) = match start {
+            (_, Some(m), d) => (m, d),
+            (_, None, _) => return Err(err()),
+        };
+        let start = match start.0 {
+            Some(y) => NaiveDate::from_ymd_opt(y, start_month, start_day),
+            None => {
+                cur.fallback(Fallback::InferredYear);
+                infer_year(start_month, start_day, today)
+            },
+        }.ok_or_else(&err)?;
+
+        cur.skip_whitespace();
+        let until = if cur.range_sep() {
+            cur.fallback(Fallback::DateRange);
+            let (y, m, d) = cur.ymd().ok_or_else(&err)?;
+            let m = m.unwrap_or(start.month());
+            let until = match y {
+                Some(y) => NaiveDate::from_ymd_opt(y, m, d),
+                // e.g. `12月28日～1月5日`
+                None if (m, d) < (start.month(), start.day()) => NaiveDate::from_ymd_opt(start.year() + 1, m, d),
+                None => NaiveDate::from_ymd_opt(start.year(), m, d),
+            }.ok_or_else(&err)?;
+            if until < start {
+                return Err(err());
+            }
+            cur.skip_whitespace();
+            Some(until)
+        } else {
+            None
+        };
+
+        // Whitespace between the date and the periods is not a fallback.
+        let rest = chars[cur.pos..].iter().cloned().collect::<String>();
 
-        let periods = src.split_whitespace().nth(1)
-            .ok_or_else::<Error,_>(|| format!("unable to find periods string in: {}", src).into())?;
-        let periods = parse_periods(periods);
+        let periods = match rest.trim() {
+            "" => Periods::Unspecified,
+            periods => parse_periods(periods).map_err(|e| (Field::Periods, e))?,
+        };
 
-        Ok((date, periods))
+        fallbacks.extend(cur.fallbacks);
+
+        Ok(((start, until), periods))
+    }
+
+    /// Returns the date of the month and day in the academic year (from April to March) of `today`. If this puts the
+    /// date more than half a year away from `today`, the adjacent academic year is used instead.
+    pub fn infer_year(month: u32, day: u32, today: NaiveDate) -> Option<NaiveDate> {
+        let academic_year = if today.month() >= 4 { today.year() } else { today.year() - 1 };
+        let year = if month >= 4 { academic_year } else { academic_year + 1 };
+
+        NaiveDate::from_ymd_opt(year, month, day).and_then(|date| {
+            let days = date.signed_duration_since(today).num_days();
+            if days > 183 {
+                NaiveDate::from_ymd_opt(year - 1, month, day)
+            } else if days < -183 {
+                NaiveDate::from_ymd_opt(year + 1, month, day)
+            } else {
+                Some(date)
+            }
+        })
+    }
+
+    struct DateCursor<'a> {
+        chars: &'a [char],
+        pos: usize,
+        fallbacks: Vec<Fallback>,
+    }
+
+    impl<'a> DateCursor<'a> {
+        /// Parses a date like `2016年04月06日(水)`, `4月6日` or `8日`, returning the year, the month and the day.
+        fn ymd(&mut self) -> Option<(Option<i32>, Option<u32>, u32)> {
+            let mut year = None;
+            let mut month = None;
+
+            self.skip_whitespace_inside();
+            let mut n = self.number();
+
+            if self.eat('年') {
+                year = n.map(|(n, _)| n as i32);
+                self.skip_whitespace_inside();
+                n = self.number();
+            }
+            if self.eat('月') {
+                month = self.padded(n);
+                if month.is_none() {
+                    return None;
+                }
+                self.skip_whitespace_inside();
+                n = self.number();
+            }
+            if !self.eat('日') {
+                return None;
+            }
+            let day = match self.padded(n) {
+                Some(d) => d,
+                None => return None,
+            };
+
+            // The day of the week, e.g. `(水)`.
+            let pos = self.pos;
+            self.skip_whitespace();
+            if self.eat('(') || self.eat('（') {
+                while self.pos < self.chars.len() && !(self.eat(')') || self.eat('）')) {
+                    self.pos += 1;
+                }
+            } else {
+                self.pos = pos;
+            }
+
+            Some((year, month, day))
+        }
+
+        /// Parses a number of ASCII or full-width digits, returning the value and the number of digits.
+        fn number(&mut self) -> Option<(u32, usize)> {
+            let start = self.pos;
+            let mut ret = 0u32;
+
+            while let Some(c) = self.chars.get(self.pos).cloned() {
+                let d = if '0' <= c && c <= '9' {
+                    c as u32 - '0' as u32
+                } else if '０' <= c && c <= '９' {
+                    self.fallback(Fallback::FullWidthDigits);
+                    c as u32 - '０' as u32
+                } else {
+                    break;
+                };
+                ret = ret.saturating_mul(10).saturating_add(d);
+                self.pos += 1;
+            }
+
+            if self.pos == start { None } else { Some((ret, self.pos - start)) }
+        }
+
+        /// Returns the value of a month or a day, recording `UnpaddedNumber` if it is not zero-padded.
+        fn padded(&mut self, n: Option<(u32, usize)>) -> Option<u32> {
+            n.map(|(n, len)| {
+                if len < 2 {
+                    self.fallback(Fallback::UnpaddedNumber);
+                }
+                n
+            })
+        }
+
+        fn fallback(&mut self, f: Fallback) {
+            if !self.fallbacks.contains(&f) {
+                self.fallbacks.push(f);
+            }
+        }
+
+        fn eat(&mut self, c: char) -> bool {
+            if self.chars.get(self.pos) == Some(&c) {
+                self.pos += 1;
+                true
+            } else {
+                false
+            }
+        }
+
+        fn range_sep(&mut self) -> bool {
+            ['〜', '～', '~', '-', '－'].iter().any(|&c| self.eat(c))
+        }
+
+        fn skip_whitespace(&mut self) {
+            while self.chars.get(self.pos).map_or(false, |c| c.is_whitespace()) {
+                self.pos += 1;
+            }
+        }
+
+        /// Skips whitespace inside a date, recording `Whitespace` if any.
+        fn skip_whitespace_inside(&mut self) {
+            let pos = self.pos;
+            self.skip_whitespace();
+            if self.pos != pos {
+                self.fallback(Fallback::Whitespace);
+            }
+        }
+    }
+
+    pub fn parse_remarks(src: &str, date: NaiveDate) -> Details {
+        const ROOM_KEYS: [&'static str; 4] = ["変更後教室", "教室変更", "教室", "場所"];
+        const DATE_KEYS: [&'static str; 5] = ["補講日", "振替日", "実施日", "変更後", "日時"];
+
+        const HOMEWORK_KEYS: [&'static str; 2] = ["課題", "レポート"];
+        const NEGATIONS: [&'static str; 4] = ["なし", "無し", "不要", "ありません"];
+
+        let mut ret = Details::default();
+
+        ret.homework = src.split(|c: char| c == '\n' || c == '。').any(|clause| {
+            HOMEWORK_KEYS.iter().any(|key| clause.contains(key)) && !NEGATIONS.iter().any(|neg| clause.contains(neg))
+        });
+
+        ret.room = src.lines().filter_map(|line| {
+            ROOM_KEYS.iter()
+                .flat_map(|key| line.match_indices(key).map(move |(i, _)| &line[i+key.len()..]))
+                .filter_map(|rest| {
+                    let rest = rest.trim_left();
+                    if !rest.starts_with('：') && !rest.starts_with(':') {
+                        return None;
+                    }
+                    let rest = rest[rest.chars().next().unwrap().len_utf8()..].trim_left();
+                    let room = rest.split(|c: char| c.is_whitespace() || "、。,，()（）にへで".contains(c))
+                        .next().unwrap();
+                    // e.g. `A101→B202`
+                    let room = room.rsplit('→').next().unwrap();
+                    if room.is_empty() { None } else { Some(room.to_owned()) }
+                })
+                .next()
+        }).next();
+
+        let mut dates = src.lines()
+            .flat_map(|line| {
+                let preferred = DATE_KEYS.iter().any(|key| line.contains(key));
+                find_dates(line, date).into_iter().map(move |(d, p)| (preferred, d, p))
+            })
+            .filter(|&(_, d, _)| d != date)
+            .collect::<Vec<_>>();
+
+        let i = dates.iter().position(|&(preferred, _, _)| preferred).unwrap_or(0);
+        if i < dates.len() {
+            let (_, d, p) = dates.swap_remove(i);
+            ret.date = Some(d);
+            ret.periods = p;
+        }
+
+        ret
+    }
+
+    /// Finds dates like `4月13日(木) 3講時` in a line, with periods immediately following them if any.
+    fn find_dates(line: &str, reference: NaiveDate) -> Vec<(NaiveDate, Option<Periods>)> {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut ret = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            if digit_value(chars[i]).is_none() || (i > 0 && digit_value(chars[i-1]).is_some()) {
+                i += 1;
+                continue;
+            }
+
+            let mut cur = DateCursor { chars: &chars, pos: i, fallbacks: Vec::new() };
+            let date = match cur.ymd() {
+                Some((Some(y), Some(m), d)) => NaiveDate::from_ymd_opt(y, m, d),
+                Some((None, Some(m), d)) => infer_year(m, d, reference),
+                _ => None,
+            };
+
+            match date {
+                Some(date) => {
+                    let rest = chars[cur.pos..].iter().cloned().collect::<String>();
+                    let periods = rest.find("講時").and_then(|end| {
+                        // Only the numbers and separators just before `講時` are regarded as periods.
+                        let start = rest[..end].char_indices()
+                            .filter(|&(_, c)| !is_period_char(c))
+                            .last()
+                            .map_or(0, |(j, c)| j + c.len_utf8());
+                        // Periods following another date belong to that date.
+                        if start < end && !rest[..start].contains('日') {
+                            parse_periods(&rest[start..end]).ok()
+                        } else {
+                            None
+                        }
+                    });
+                    ret.push((date, periods));
+                    i = cur.pos;
+                },
+                None => i += 1,
+            }
+        }
+
+        ret
     }
 
     pub fn parse_kamoku(src: &str) -> Result<(String, String)> {
@@ -139,59 +615,295 @@ mod util {
         Ok((title.to_string(), lecturer.to_string()))
     }
 
-    pub fn parse_date_str(src: &str) -> Result<NaiveDate> {
-        NaiveDate::parse_from_str(src, "%Y年%m月%d日").chain_err(|| "unable to parse the date")
-    }
-
-    pub fn parse_periods(src: &str) -> Periods {
-        fn isdigit(c: u8) -> bool { b'0' <= c && c <= b'9' }
-        fn atoi(c: u8) -> u8 { c - b'0' }
+    /// Parses a string representing periods, e.g. `1-3,5講時`.
+    ///
+    /// The numbers may be written in ASCII digits, full-width digits or kanji numerals, and ranges are denoted by
+    /// `-`, `〜` or `～`. Any other character (e.g. `,`, `・` or `講時`) is regarded as a separator.
+    ///
+    /// Strings containing `終日`, `午前`, `午後`, `集中` or `未定` are parsed into the corresponding non-numbered
+    /// periods.
+    pub fn parse_periods(src: &str) -> Result<Periods> {
+        if src.contains("終日") {
+            return Ok(Periods::AllDay);
+        } else if src.contains("午前") {
+            return Ok(Periods::Morning);
+        } else if src.contains("午後") {
+            return Ok(Periods::Afternoon);
+        } else if src.contains("集中") || src.contains("未定") {
+            return Ok(Periods::Unspecified);
+        }
 
-        let mut ret = Vec::new();
+        enum Token {
+            Num(u8),
+            Range,
+        }
 
-        macro_rules! push {
-            ($c:expr) => {{
-                if isdigit($c) {
-                    ret.push(atoi($c));
+        let err = || -> Error { format!("unable to parse periods: {}", src).into() };
+
+        let chars = src.chars().collect::<Vec<_>>();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            if digit_value(chars[i]).is_some() {
+                let mut n = 0u32;
+                while let Some(d) = chars.get(i).cloned().and_then(digit_value) {
+                    n = n * 10 + d;
+                    if n > u8::MAX as u32 {
+                        return Err(err());
+                    }
+                    i += 1;
+                }
+                tokens.push(Token::Num(n as u8));
+            } else if is_kanji_numeral(chars[i]) {
+                let start = i;
+                while i < chars.len() && is_kanji_numeral(chars[i]) {
+                    i += 1;
+                }
+                let n = parse_kanji_numeral(&chars[start..i]).ok_or_else(&err)?;
+                tokens.push(Token::Num(n));
+            } else {
+                if ['-', '－', '‐', '~', '〜', '～'].contains(&chars[i]) {
+                    tokens.push(Token::Range);
                 }
-            }}
+                i += 1;
+            }
         }
 
-        for a in src.as_bytes().split(|&c| !isdigit(c) && c != b'-') {
-            for w in a.windows(3) {
-                match *w {
-                    [c, b'-', d] if isdigit(c) && isdigit(d) => ret.extend(atoi(c)..(atoi(d)+1)),
-                    [_, _, c] => push!(c),
-                    _ => unreachable!(), // `windows` returns nothing if `slice.len() < 3`.
-                }
+        let mut ret = Vec::new();
+        let mut last = None;
+        let mut in_range = false;
+
+        for t in tokens {
+            match t {
+                Token::Num(n) => {
+                    if in_range {
+                        let start = last.ok_or_else(&err)?;
+                        if start > n {
+                            return Err(err());
+                        }
+                        ret.extend((start as u32..n as u32 + 1).map(|n| n as u8));
+                        in_range = false;
+                    } else {
+                        ret.push(n);
+                    }
+                    last = Some(n);
+                },
+                Token::Range => {
+                    if in_range || last.is_none() {
+                        return Err(err());
+                    }
+                    in_range = true;
+                },
             }
+        }
 
-            if let Some(&c) = a.get(0) { push!(c); }
-            if let Some(&c) = a.get(1) { push!(c); }
+        if in_range || ret.is_empty() {
+            return Err(err());
         }
 
-        Periods::from(ret)
+        Ok(Periods::from(ret))
+    }
+
+    /// Returns the value of an ASCII or full-width digit.
+    fn digit_value(c: char) -> Option<u32> {
+        if '0' <= c && c <= '9' {
+            Some(c as u32 - '0' as u32)
+        } else if '０' <= c && c <= '９' {
+            Some(c as u32 - '０' as u32)
+        } else {
+            None
+        }
+    }
+
+    const KANJI_DIGITS: [char; 10] = ['〇', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+    fn is_period_char(c: char) -> bool {
+        digit_value(c).is_some() || is_kanji_numeral(c) || ",.・、，〜～-－ ".contains(c)
+    }
+
+    fn is_kanji_numeral(c: char) -> bool {
+        c == '十' || KANJI_DIGITS.contains(&c)
+    }
+
+    /// Parses a kanji numeral less than 100, e.g. `三`, `十` or `十二`.
+    fn parse_kanji_numeral(s: &[char]) -> Option<u8> {
+        fn digit(c: char) -> Option<u8> {
+            KANJI_DIGITS.iter().position(|&d| d == c).map(|n| n as u8)
+        }
+
+        match s.iter().position(|&c| c == '十') {
+            Some(i) => {
+                let tens = match s[..i].len() {
+                    0 => Some(1),
+                    1 => digit(s[0]),
+                    _ => None,
+                };
+                let ones = match s[i+1..].len() {
+                    0 => Some(0),
+                    1 => digit(s[i+1]),
+                    _ => None,
+                };
+                match (tens, ones) {
+                    (Some(tens), Some(ones)) => Some(tens * 10 + ones),
+                    _ => None,
+                }
+            },
+            None if s.len() == 1 => digit(s[0]),
+            None => None,
+        }
     }
 
     #[cfg(test)]
     mod tests {
         use super::*;
 	    use chrono::NaiveDate;
-        use ::Periods;
+        use kuchiki::parse_html;
+        use kuchiki::traits::*;
+        use scraper::{Fallback, Tab};
+        use ::{Details, KyukoKind, Periods};
+
+        #[test]
+        fn parse_tab_test() {
+            let document = parse_html().one("\
+                <div class=\"gakubulist\">\
+                    <a href=\"index-student2.php?g=3\" class=\"tab selected\">教養教育<br>&nbsp;<br>60件</a>\
+                    <a href=\"index-student2.php?g=4\" class=\"tab\">人文学部<br>（研究科）<br>1件</a>\
+                </div>\
+            ");
+            let tabs = document.select("a.tab").unwrap()
+                .map(|a| parse_tab(a.as_node()).unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(tabs, [
+                Tab {
+                    name: "教養教育".to_owned(),
+                    suffix: None,
+                    count: Some(60),
+                    href: "index-student2.php?g=3".to_owned(),
+                    selected: true,
+                },
+                Tab {
+                    name: "人文学部".to_owned(),
+                    suffix: Some("研究科".to_owned()),
+                    count: Some(1),
+                    href: "index-student2.php?g=4".to_owned(),
+                    selected: false,
+                },
+            ]);
+        }
+
+        #[test]
+        fn parse_kyuko_tbody_test() {
+            let document = parse_html().one("\
+                <table class=\"citem\"><tbody>\
+                    <tr>\
+                        <td rowspan=\"2\"><img src=\"img/kyuko.png\" alt=\"【休講】\" width=\"60px\"></td>\
+                        <td class=\"date\">2016年04月06日(水)　<font color=\"red\">1.2講時</font></td>\
+                        <td class=\"kamoku\">線形代数<br>[山田 太郎]</td>\
+                    </tr>\
+                    <tr>\
+                        <td class=\"memo\" colspan=\"2\"> 補講日：<span>4月13日</span><br>\
+                            教室：  A101 </td>\
+                    </tr>\
+                </tbody></table>\
+            ");
+            let tbody = document.select("tbody").unwrap().next().unwrap();
+            let k = parse_kyuko_tbody(tbody.as_node(), NaiveDate::from_ymd(2016, 4, 1), &mut Vec::new()).unwrap();
+
+            assert_eq!(k.kind, KyukoKind::Cancellation);
+            assert_eq!(k.date, NaiveDate::from_ymd(2016, 4, 6));
+            assert_eq!(k.periods, Periods::from(vec![1, 2]));
+            assert_eq!(k.title, "線形代数");
+            assert_eq!(k.lecturer, "山田 太郎");
+            assert_eq!(k.remarks, Some("補講日：4月13日\n教室： A101".to_owned()));
+        }
 
         #[test]
         fn parse_date_test() {
-            assert_eq!(
-                parse_date("2016年12月19日(月)　1講時").unwrap(),
-                (NaiveDate::from_ymd(2016, 12, 19), Periods::from(vec![1u8]))
-            );
+            let today = NaiveDate::from_ymd(2016, 12, 1);
+
+            macro_rules! test_eq {
+                ($src:expr, $date:expr, $until:expr, $periods:expr, $fallbacks:expr) => {{
+                    let mut fallbacks = Vec::new();
+                    assert_eq!(parse_date($src, today, &mut fallbacks).unwrap(), (($date, $until), $periods));
+                    assert_eq!(fallbacks, &$fallbacks as &[Fallback]);
+                }};
+            }
+
+            let ymd = NaiveDate::from_ymd;
+            let p = |n| Periods::from(vec![n]);
+
+            test_eq!("2016年12月19日(月)　1講時", ymd(2016, 12, 19), None, p(1), []);
+            test_eq!("2016年12月19日(月)　終日", ymd(2016, 12, 19), None, Periods::AllDay, []);
+            test_eq!("2016年12月19日(月)", ymd(2016, 12, 19), None, Periods::Unspecified, []);
+            test_eq!("12月19日(月)　1講時", ymd(2016, 12, 19), None, p(1), [Fallback::InferredYear]);
+            test_eq!("2月1日(水)　1講時", ymd(2017, 2, 1), None, p(1), [
+                Fallback::UnpaddedNumber, Fallback::InferredYear,
+            ]);
+            test_eq!("２０１６年１２月１９日(月)　1講時", ymd(2016, 12, 19), None, p(1), [Fallback::FullWidthDigits]);
+            test_eq!("2016年 12月 19日 (月) 1講時", ymd(2016, 12, 19), None, p(1), [Fallback::Whitespace]);
+            test_eq!("2016年12月19日(月)～21日(水)　1講時", ymd(2016, 12, 19), Some(ymd(2016, 12, 21)), p(1), [
+                Fallback::DateRange,
+            ]);
+            test_eq!("2016年12月28日～1月5日", ymd(2016, 12, 28), Some(ymd(2017, 1, 5)), Periods::Unspecified, [
+                Fallback::DateRange, Fallback::UnpaddedNumber,
+            ]);
+
+            assert!(parse_date("2016年12月32日(月)　1講時", today, &mut Vec::new()).is_err());
+            assert!(parse_date("19日(月)　1講時", today, &mut Vec::new()).is_err());
+            assert!(parse_date("2016年12月19日～18日", today, &mut Vec::new()).is_err());
+        }
+
+        #[test]
+        fn infer_year_test() {
+            let ymd = NaiveDate::from_ymd;
+
+            assert_eq!(infer_year(4, 6, ymd(2017, 3, 20)), Some(ymd(2017, 4, 6)));
+            assert_eq!(infer_year(3, 30, ymd(2017, 4, 5)), Some(ymd(2017, 3, 30)));
+            assert_eq!(infer_year(1, 10, ymd(2016, 12, 1)), Some(ymd(2017, 1, 10)));
+            assert_eq!(infer_year(10, 1, ymd(2016, 12, 1)), Some(ymd(2016, 10, 1)));
+        }
+
+        #[test]
+        fn parse_remarks_test() {
+            let date = NaiveDate::from_ymd(2017, 4, 6);
+            let d = parse_remarks("補講日：4月13日(木) 3講時\n教室：A101\nレポート課題あり", date);
+
+            assert_eq!(d, Details {
+                room: Some("A101".to_owned()),
+                date: Some(NaiveDate::from_ymd(2017, 4, 13)),
+                periods: Some(Periods::from(vec![3])),
+                homework: true,
+            });
+
+            let d = parse_remarks("教室変更: B202（4月6日のみ）", date);
+            assert_eq!(d, Details { room: Some("B202".to_owned()), ..Details::default() });
+
+            // Without a separator, the text following `教室` is not regarded as a room.
+            assert_eq!(parse_remarks("4月6日の授業は教室をB202に変更します。", date).room, None);
+            assert_eq!(parse_remarks("教室変更はありません", date), Details::default());
+
+            assert!(!parse_remarks("課題なし", date).homework);
+            assert!(!parse_remarks("レポート提出不要", date).homework);
+            assert!(!parse_remarks("課題はありません。", date).homework);
+            assert!(parse_remarks("休講。課題あり（詳細は掲示を参照）", date).homework);
+
+            let d = parse_remarks("4月6日分の補講を5月11日（木）に実施（1・2講時）", date);
+            assert_eq!(d.date, Some(NaiveDate::from_ymd(2017, 5, 11)));
+            assert_eq!(d.periods, Some(Periods::from(vec![1, 2])));
+
+            let d = parse_remarks("教室変更：A101→B202", date);
+            assert_eq!(d.room, Some("B202".to_owned()));
+
+            assert_eq!(parse_remarks("特になし", date), Details::default());
         }
 
         #[test]
         fn parse_periods_test() {
             macro_rules! test_eq {
                 ($src:expr, $expect:expr) => {{
-                    assert_eq!(parse_periods($src).as_ref(), &$expect);
+                    assert_eq!(parse_periods($src).unwrap().as_ref(), &$expect);
                 }};
             }
 
@@ -203,7 +915,72 @@ mod util {
             test_eq!("3-5講時", [3, 4, 5]);
             test_eq!("1-3,5講時", [1, 2, 3, 5]);
             test_eq!("1,3-5,6講時", [1, 3, 4, 5, 6]);
+            test_eq!("10講時", [10]);
+            test_eq!("9,10講時", [9, 10]);
+            test_eq!("１・２講時", [1, 2]);
+            test_eq!("１０講時", [10]);
+            test_eq!("一講時", [1]);
+            test_eq!("三・四講時", [3, 4]);
+            test_eq!("十二講時", [12]);
+            test_eq!("1〜3講時", [1, 2, 3]);
+            test_eq!("２～４講時", [2, 3, 4]);
+            test_eq!("1・3〜5, 7講時", [1, 3, 4, 5, 7]);
+            test_eq!("3講時・4講時", [3, 4]);
+        }
+
+        #[test]
+        fn parse_periods_non_numbered_test() {
+            assert_eq!(parse_periods("終日").unwrap(), Periods::AllDay);
+            assert_eq!(parse_periods("午前").unwrap(), Periods::Morning);
+            assert_eq!(parse_periods("午後").unwrap(), Periods::Afternoon);
+            assert_eq!(parse_periods("集中講義").unwrap(), Periods::Unspecified);
+        }
+
+        #[test]
+        fn parse_periods_err_test() {
+            assert!(parse_periods("講時").is_err());
+            assert!(parse_periods("").is_err());
+            assert!(parse_periods("-3講時").is_err());
+            assert!(parse_periods("3-講時").is_err());
+            assert!(parse_periods("5-3講時").is_err());
+            assert!(parse_periods("256講時").is_err());
         }
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(count: Option<u32>, kyukos: usize, failures: usize) -> Page {
+        let failure = RowFailure {
+            index: 0,
+            field: Field::Date,
+            message: "invalid date".to_owned(),
+            html: String::new(),
+        };
+
+        Page {
+            tabs: vec![Tab {
+                name: "教養教育".to_owned(),
+                suffix: None,
+                count: count,
+                href: "index-student2.php?g=3".to_owned(),
+                selected: true,
+            }],
+            kyukos: vec![::tests::kyuko(); kyukos],
+            failures: vec![failure; failures],
+            fallbacks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn count_mismatch() {
+        assert_eq!(page(Some(3), 3, 0).count_mismatch(), None);
+        assert_eq!(page(Some(3), 2, 1).count_mismatch(), None);
+        assert_eq!(page(Some(3), 2, 0).count_mismatch(), Some(3));
+        assert_eq!(page(Some(0), 1, 0).count_mismatch(), Some(0));
+        assert_eq!(page(None, 2, 0).count_mismatch(), None);
+    }
+}
@@ -0,0 +1,137 @@
+use chrono::NaiveTime;
+use serde::{Deserialize, Deserializer};
+use ::Periods;
+
+/// A timetable mapping periods to the time of day.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Timetable {
+    /// The start and end time of each period, beginning with the 1st period.
+    periods: Vec<(NaiveTime, NaiveTime)>,
+}
+
+impl Timetable {
+    pub fn new(periods: Vec<(NaiveTime, NaiveTime)>) -> Self {
+        Timetable { periods: periods }
+    }
+
+    /// Returns the start and end time of the `n`th period.
+    pub fn period(&self, n: u8) -> Option<(NaiveTime, NaiveTime)> {
+        if n == 0 {
+            None
+        } else {
+            self.periods.get(n as usize - 1).cloned()
+        }
+    }
+
+    /// Returns the time ranges of `periods`, each of which covers a run of consecutive periods, e.g. two ranges for
+    /// `1・3講時`.
+    ///
+    /// `Periods::Morning` and `Periods::Afternoon` are regarded as the periods they cover. Returns `None` if
+    /// `periods` covers no period in the timetable or includes a period missing from it.
+    pub fn ranges(&self, periods: &Periods) -> Option<Vec<(NaiveTime, NaiveTime)>> {
+        if let Periods::Numbered(ref v) = *periods {
+            if v.iter().any(|&n| self.period(n).is_none()) {
+                return None;
+            }
+        }
+
+        // The first and last periods of each run
+        let mut runs: Vec<(u8, u8)> = Vec::new();
+        for n in (1..self.periods.len() as u8 + 1).filter(|&n| periods.covers(n)) {
+            if runs.last().map_or(false, |&(_, last)| last + 1 == n) {
+                runs.last_mut().unwrap().1 = n;
+            } else {
+                runs.push((n, n));
+            }
+        }
+
+        if runs.is_empty() {
+            None
+        } else {
+            Some(runs.into_iter().map(|(first, last)| (self.period(first).unwrap().0, self.period(last).unwrap().1))
+                .collect())
+        }
+    }
+
+    /// Returns the time from the start of the first period to the end of the last period of `periods` if they are
+    /// consecutive. See `ranges` for details.
+    pub fn range(&self, periods: &Periods) -> Option<(NaiveTime, NaiveTime)> {
+        self.ranges(periods).and_then(|r| if r.len() == 1 { Some(r[0]) } else { None })
+    }
+}
+
+/// The timetable of the Mito campus.
+impl Default for Timetable {
+    fn default() -> Self {
+        let hm = |h, m| NaiveTime::from_hms(h, m, 0);
+
+        Timetable::new(vec![
+            (hm( 8, 50), hm(10, 20)),
+            (hm(10, 30), hm(12,  0)),
+            (hm(12, 50), hm(14, 20)),
+            (hm(14, 30), hm(16,  0)),
+            (hm(16, 10), hm(17, 40)),
+            (hm(17, 50), hm(19, 20)),
+            (hm(19, 30), hm(21,  0)),
+        ])
+    }
+}
+
+/// Deserializes a sequence of strings like `08:50-10:20`, beginning with the 1st period.
+impl Deserialize for Timetable {
+    fn deserialize<D: Deserializer>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        fn parse(s: &str) -> Option<(NaiveTime, NaiveTime)> {
+            let mut split = s.splitn(2, '-').map(|t| NaiveTime::parse_from_str(t.trim(), "%H:%M"));
+            match (split.next(), split.next()) {
+                (Some(Ok(start)), Some(Ok(end))) if start <= end => Some((start, end)),
+                _ => None,
+            }
+        }
+
+        Vec::<String>::deserialize(d)?.iter()
+            .map(|s| parse(s).ok_or_else(|| D::Error::custom(format!("invalid time range: {}", s))))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Timetable::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range() {
+        let hm = |h, m| NaiveTime::from_hms(h, m, 0);
+        let t = Timetable::default();
+
+        assert_eq!(t.range(&Periods::from(vec![3])), Some((hm(12, 50), hm(14, 20))));
+        assert_eq!(t.range(&Periods::from(vec![1, 2])), Some((hm(8, 50), hm(12, 0))));
+        assert_eq!(t.range(&Periods::Morning), Some((hm(8, 50), hm(12, 0))));
+        assert_eq!(t.range(&Periods::AllDay), Some((hm(8, 50), hm(21, 0))));
+        assert_eq!(t.range(&Periods::Unspecified), None);
+        assert_eq!(t.range(&Periods::from(vec![8])), None);
+        assert_eq!(t.range(&Periods::from(vec![7, 8])), None);
+        assert_eq!(t.range(&Periods::from(vec![1, 3])), None);
+    }
+
+    #[test]
+    fn ranges() {
+        let hm = |h, m| NaiveTime::from_hms(h, m, 0);
+        let t = Timetable::default();
+
+        assert_eq!(t.ranges(&Periods::from(vec![1, 2])), Some(vec![(hm(8, 50), hm(12, 0))]));
+        assert_eq!(
+            t.ranges(&Periods::from(vec![1, 3, 4])),
+            Some(vec![(hm(8, 50), hm(10, 20)), (hm(12, 50), hm(16, 0))])
+        );
+        assert_eq!(t.ranges(&Periods::from(vec![0])), None);
+        assert_eq!(t.ranges(&Periods::from(vec![6, 8])), None);
+        assert_eq!(t.ranges(&Periods::Unspecified), None);
+
+        let t = Timetable::new(vec![(hm(9, 0), hm(10, 30)), (hm(10, 40), hm(12, 10))]);
+        assert_eq!(t.ranges(&Periods::Afternoon), None);
+        assert_eq!(t.ranges(&Periods::AllDay), Some(vec![(hm(9, 0), hm(12, 10))]));
+    }
+}